@@ -0,0 +1,48 @@
+use crate::bot::{Bot, BotConfig};
+use crate::Board;
+use crate::{STARTING_FEN, KIWIPETE_FEN, TEST_POSITION_4, MATE_IN_5_FEN, ENDGAME_POSITION, PAWN_ENDGAME_FEN, DRAWN_ENDGAME_FEN, PAWN_EVAL_TESTING};
+use std::time::Instant;
+
+pub const BENCH_DEPTH: u8 = 8;
+pub const BENCH_HASH_SIZE: usize = 16;
+
+pub const BENCH_FENS: [&str; 10] = [
+	STARTING_FEN,
+	KIWIPETE_FEN,
+	TEST_POSITION_4,
+	"r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+	"r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 8",
+	MATE_IN_5_FEN,
+	ENDGAME_POSITION,
+	PAWN_ENDGAME_FEN,
+	DRAWN_ENDGAME_FEN,
+	PAWN_EVAL_TESTING,
+];
+
+// Searches a fixed set of positions to a fixed depth, so that changes to the search can be compared by node count
+pub fn bench(config: &BotConfig, depth: u8) {
+	let mut config = config.clone();
+	config.debug_output = false;
+	config.opening_book = false;
+	config.hash_size = BENCH_HASH_SIZE;
+	// Tablebases would change the node counts depending on what's installed
+	config.syzygy_path = String::new();
+	config.tablebase_path = String::new();
+	config.experience_file = String::new();
+
+	let mut total_nodes = 0;
+	let timer = Instant::now();
+
+	for fen in BENCH_FENS {
+		let mut board = Board::from_fen(fen);
+		let mut bot = Bot::new(config.clone());
+		board.set_eval_params(bot.eval_params.clone());
+
+		bot.start(&mut board, 0.0, depth);
+		total_nodes += bot.total_nodes();
+	}
+
+	let time_elapsed = timer.elapsed().as_secs_f32();
+
+	println!("\n{} nodes {} nps", total_nodes, (total_nodes as f32 / time_elapsed) as u128);
+}
@@ -1,19 +1,28 @@
 use crate::nnue::{self, NNUE, NNUE_EVAL_SCALE};
 use crate::value_holder::ValueHolder;
 use crate::utils::{pop_lsb, get_lsb, print_bitboard, coordinate_to_index};
-use crate::piece_square_tables::{BASE_WORTHS_OF_PIECE_TYPE, get_full_worth_of_piece, ROOK_WORTH, BISHOP_WORTH};
+use crate::piece_square_tables::{BASE_WORTHS_OF_PIECE_TYPE, ROOK_WORTH};
 use crate::precalculated_move_data::*;
 use crate::move_data::*;
+use crate::move_list::MoveList;
 use crate::zobrist::Zobrist;
+use crate::endgames::Endgames;
+use crate::pawn_structure::PawnHashTable;
+use crate::eval_params::EvalParams;
+use crate::endgame_tablebase::EndgameTablebases;
+use crate::eco::{EcoOpening, eco_table};
 use crate::pieces::*;
 use crate::castling_rights::*;
 use colored::Colorize;
-
-pub const MAX_ENDGAME_MATERIAL: f32 = (ROOK_WORTH * 2 + BISHOP_WORTH * 2) as f32;
-
-pub const DOUBLED_PAWN_PENALTY: i32 = 35; // TODO
-pub const ISOLATED_PAWN_PENALTY: i32 = 20; // TODO
-pub const PASSED_PAWN_BOOST: [i32; 8] = [0, 15, 15, 30, 50, 90, 150, 0]; // TODO
+use std::sync::Arc;
+
+// Which moves the move generator should return, so that the move picker can generate captures and quiets separately
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MoveGenType {
+	All,
+	Captures,
+	Quiets,
+}
 
 #[derive(Copy, Clone)]
 pub struct BoardState {
@@ -34,6 +43,7 @@ impl BoardState {
 
 pub struct Board {
 	pub precalculated_move_data: PrecalculatedMoveData,
+	pub endgames: Endgames,
 
 	pub piece_bitboards: [u64; PIECE_COUNT],
 	pub color_bitboards: [u64; 2],
@@ -44,6 +54,8 @@ pub struct Board {
 	pub total_material_without_pawns: [i32; 2],
 
 	pub zobrist: Zobrist,
+	pub pawn_hash: PawnHashTable,
+	pub eval_params: EvalParams,
 
 	pub moves: Vec<MoveData>,
 
@@ -51,7 +63,12 @@ pub struct Board {
 
 	pub board_state: ValueHolder<BoardState>,
 
-	// pub nnue: NNUE,
+	pub nnue: NNUE,
+	// The accumulator is only kept up to date when the NNUE is the evaluation being used
+	pub nnue_enabled: bool,
+
+	// Maxwell's own tablebases, shared with the Bot so the evaluation can probe them too
+	pub tablebases: Option<Arc<EndgameTablebases>>,
 }
 
 impl Board {
@@ -69,6 +86,7 @@ impl Board {
 
 		let mut board = Self {
 			precalculated_move_data: PrecalculatedMoveData::calculate(),
+			endgames: Endgames::new(),
 
 			piece_bitboards: [0; PIECE_COUNT],
 			color_bitboards: [0; 2],
@@ -80,12 +98,17 @@ impl Board {
 			total_material_without_pawns: [0, 0],
 
 			zobrist: Zobrist::default(),
+			pawn_hash: PawnHashTable::default(),
+			eval_params: EvalParams::default(),
 
 			moves: vec![],
 
 			board_state: ValueHolder::new(BoardState::new(castling_rights, fifty_move_counter)),
 
-			// nnue: NNUE::new(),
+			nnue: NNUE::new(),
+			nnue_enabled: false,
+
+			tablebases: None,
 		};
 
 		let piece_rows = fen[0].split('/').collect::<Vec<&str>>();
@@ -117,8 +140,6 @@ impl Board {
 		board.zobrist = Zobrist::generate(&board);
 		board.calculate_attacked_squares();
 
-		// board.nnue = NNUE::initialize(&board);
-
 		board
 	}
 
@@ -234,6 +255,106 @@ impl Board {
 		NO_PIECE
 	}
 
+	pub fn material_counts(&self) -> [[u8; 6]; 2] {
+		let mut counts = [[0; 6]; 2];
+		for (side, is_white) in [(0, true), (1, false)] {
+			for (piece_type, count) in counts[side].iter_mut().enumerate() {
+				*count = self.piece_bitboards[build_piece(is_white, piece_type)].count_ones() as u8;
+			}
+		}
+		counts
+	}
+
+	// White's pieces first, see pieces::material_key
+	pub fn material_key(&self) -> u64 {
+		material_key(&self.material_counts())
+	}
+
+	// The ECO opening of the deepest position in this game that's in the table, so a game that left the
+	// known lines keeps the name of the last one it was in
+	pub fn find_opening(&mut self) -> Option<&'static EcoOpening> {
+		let table = eco_table();
+		let mut undone_moves = vec![];
+
+		let mut opening = table.get(self);
+		while opening.is_none() {
+			let last_move = self.get_last_move();
+			if !self.undo_last_move() {
+				break;
+			}
+
+			undone_moves.push(last_move);
+			opening = table.get(self);
+		}
+
+		for data in undone_moves.into_iter().rev() {
+			self.make_move(data);
+		}
+
+		opening
+	}
+
+	// The pawn hash table was filled with the old parameters, so it has to be cleared
+	pub fn set_eval_params(&mut self, eval_params: EvalParams) {
+		self.eval_params = eval_params;
+		self.pawn_hash = PawnHashTable::default();
+	}
+
+	// Replaces every piece on the board and clears the move history, so positions can be set up quickly without a FEN
+	pub fn set_pieces(&mut self, pieces: &[(usize, u8)], white_to_move: bool) {
+		self.piece_bitboards = [0; PIECE_COUNT];
+		self.color_bitboards = [0; 2];
+		self.total_material_without_pawns = [0, 0];
+
+		for (piece, square) in pieces.iter().copied() {
+			self.piece_bitboards[piece] |= 1 << square;
+			self.color_bitboards[is_piece_white(piece) as usize] |= 1 << square;
+
+			let piece_type = get_piece_type(piece);
+			if piece_type != PAWN
+			&& piece_type != KING {
+				self.total_material_without_pawns[is_piece_white(piece) as usize] += BASE_WORTHS_OF_PIECE_TYPE[piece_type];
+			}
+		}
+
+		self.white_to_move = white_to_move;
+		self.moves.clear();
+
+		self.board_state.index = 0;
+		self.board_state.current = BoardState::new(0, 0);
+		self.board_state.history[0] = self.board_state.current;
+
+		let (key, pawn_key) = self.zobrist.calculate_keys(self);
+		self.zobrist.key.index = 0;
+		self.zobrist.key.current = key;
+		self.zobrist.key.history[0] = key;
+		self.zobrist.pawn_key.index = 0;
+		self.zobrist.pawn_key.current = pawn_key;
+		self.zobrist.pawn_key.history[0] = pawn_key;
+
+		if self.nnue_enabled {
+			self.nnue = NNUE::initialize(self);
+		}
+	}
+
+	pub fn set_nnue(&mut self, enabled: bool) {
+		if enabled
+		&& !self.nnue_enabled {
+			// Replays the game from the start, so every move that can be undone has an accumulator to go back to
+			let moves = self.moves.clone();
+			while self.undo_last_move() {}
+
+			self.nnue = NNUE::initialize(self);
+			self.nnue_enabled = true;
+
+			for data in moves {
+				self.make_move(data);
+			}
+		}
+
+		self.nnue_enabled = enabled;
+	}
+
 	pub fn occupied_bitboard(&self) -> u64 { self.color_bitboards[0] | self.color_bitboards[1] }
 	pub fn unoccupied_bitboard(&self) -> u64 { !self.occupied_bitboard() }
 
@@ -244,8 +365,9 @@ impl Board {
 	pub fn play_move(&mut self, data: MoveData) -> bool {
 		let promoting = PROMOTABLE.contains(&data.flag);
 		if self.white_to_move == is_piece_white(self.get_piece(data.from)) {
-			let moves = self.get_moves_for_piece(data.from, false);
-			for m in moves {
+			let mut moves = MoveList::new();
+			self.get_moves_for_piece(data.from, MoveGenType::All, &mut moves);
+			for m in moves.iter().copied() {
 				if (!promoting || data.flag == m.flag)
 				&& m.from == data.from
 				&& m.to == data.to
@@ -371,7 +493,9 @@ impl Board {
 			self.board_state.history[self.board_state.index - 1].castling_rights,
 		);
 
-		// self.nnue.make_move(&data);
+		if self.nnue_enabled {
+			self.nnue.make_move(&data);
+		}
 
 		self.moves.push(data);
 		self.white_to_move = !self.white_to_move;
@@ -457,9 +581,12 @@ impl Board {
 		}
 
 		self.board_state.pop();
-		self.zobrist.key.pop();
+		self.zobrist.undo_move();
 
-		// self.nnue.undo_move(&last_move);
+		if self.nnue_enabled {
+			debug_assert!(self.nnue.accumulators.len() > 1, "Undoing a move that has no accumulator");
+			self.nnue.undo_move();
+		}
 
 		self.white_to_move = !self.white_to_move;
 
@@ -471,8 +598,7 @@ impl Board {
 		self.piece_bitboards[build_piece(king_is_white, KING)] & attacked_squares != 0
 	}
 
-	pub fn get_pseudo_legal_moves_for_color(&mut self, white_pieces: bool, only_captures: bool) -> Vec<MoveData> {
-		let mut result = vec![];
+	pub fn get_pseudo_legal_moves_for_color(&mut self, white_pieces: bool, gen_type: MoveGenType, moves: &mut MoveList) {
 
 		let pieces = if white_pieces {
 			WHITE_PAWN..=WHITE_KING
@@ -485,25 +611,22 @@ impl Board {
 
 			while bitboard != 0 {
 				let piece_index = pop_lsb(&mut bitboard);
-				result.extend(self.get_moves_for_piece(piece_index, only_captures));
+				self.get_moves_for_piece(piece_index, gen_type, moves);
 			}
 		}
 
-		// for i in (0..result.len()).rev() {
-		// 	self.make_move(result[i]);
+		// for i in (0..moves.len()).rev() {
+		// 	self.make_move(moves[i]);
 
 		// 	if self.king_in_check(!self.white_to_move) {
-		// 		result.remove(i);
+		// 		moves.remove(i);
 		// 	}
 
 		// 	self.undo_last_move();
 		// }
-
-		result
 	}
 
-	pub fn get_moves_for_piece(&mut self, piece_index: u8, only_captures: bool) -> Vec<MoveData> {
-		let mut result = vec![];
+	pub fn get_moves_for_piece(&mut self, piece_index: u8, gen_type: MoveGenType, moves: &mut MoveList) {
 
 		let piece = self.get_piece(piece_index);
 		// if piece == NO_PIECE {
@@ -522,11 +645,11 @@ impl Board {
 
 
 					// Pushing
-					if !only_captures
+					if gen_type != MoveGenType::Captures
 					&& self.square_is_empty(piece_index - 8) {
 						if will_promote {
 							for promotion in KNIGHT..=QUEEN {
-								result.push(
+								moves.push(
 									MoveData {
 										flag: promotion as u8,
 										capture: NO_PIECE as u8,
@@ -537,7 +660,7 @@ impl Board {
 								);
 							}
 						} else {
-							result.push(
+							moves.push(
 								MoveData {
 									flag: 0,
 									capture: NO_PIECE as u8,
@@ -549,7 +672,7 @@ impl Board {
 
 							if rank == 6
 							&& self.square_is_empty(piece_index - 16) {
-								result.push(
+								moves.push(
 									MoveData {
 										flag: DOUBLE_PAWN_PUSH_FLAG,
 										capture: NO_PIECE as u8,
@@ -562,6 +685,10 @@ impl Board {
 						}
 					}
 
+					if gen_type == MoveGenType::Quiets {
+						return;
+					}
+
 					// Captures
 					let mut capture_bitboard =
 						  self.precalculated_move_data.pawn_attacks[1][piece_index as usize]
@@ -570,7 +697,7 @@ impl Board {
 						let capture_index = pop_lsb(&mut capture_bitboard);
 						if will_promote {
 							for promotion in KNIGHT..=QUEEN {
-								result.push(
+								moves.push(
 									MoveData {
 										flag: promotion as u8,
 										capture: self.get_piece(capture_index) as u8,
@@ -581,7 +708,7 @@ impl Board {
 								);
 							}
 						} else {
-							result.push(
+							moves.push(
 								MoveData {
 									flag: 0,
 									capture: self.get_piece(capture_index) as u8,
@@ -598,7 +725,7 @@ impl Board {
 					if last_move.flag == DOUBLE_PAWN_PUSH_FLAG {
 						if piece_index % 8 != 0
 						&& last_move.to == piece_index - 1 { // Left
-							result.push(
+							moves.push(
 								MoveData {
 									flag: EN_PASSANT_FLAG,
 									capture: self.get_piece(piece_index - 1) as u8,
@@ -609,7 +736,7 @@ impl Board {
 							);
 						} else if piece_index % 8 != 7
 						&& last_move.to == piece_index + 1 { // Right
-							result.push(
+							moves.push(
 								MoveData {
 									flag: EN_PASSANT_FLAG,
 									capture: self.get_piece(piece_index + 1) as u8,
@@ -632,11 +759,11 @@ impl Board {
 
 
 					// Pushing
-					if !only_captures
+					if gen_type != MoveGenType::Captures
 					&& self.square_is_empty(piece_index + 8) {
 						if will_promote {
 							for promotion in KNIGHT..=QUEEN {
-								result.push(
+								moves.push(
 									MoveData {
 										flag: promotion as u8,
 										capture: NO_PIECE as u8,
@@ -647,7 +774,7 @@ impl Board {
 								);
 							}
 						} else {
-							result.push(
+							moves.push(
 								MoveData {
 									flag: 0,
 									capture: NO_PIECE as u8,
@@ -659,7 +786,7 @@ impl Board {
 
 							if rank == 1
 							&& self.square_is_empty(piece_index + 16) {
-								result.push(
+								moves.push(
 									MoveData {
 										flag: DOUBLE_PAWN_PUSH_FLAG,
 										capture: NO_PIECE as u8,
@@ -672,6 +799,10 @@ impl Board {
 						}
 					}
 
+					if gen_type == MoveGenType::Quiets {
+						return;
+					}
+
 					// Captures
 					let mut capture_bitboard =
 						  self.precalculated_move_data.pawn_attacks[0][piece_index as usize]
@@ -680,7 +811,7 @@ impl Board {
 						let capture_index = pop_lsb(&mut capture_bitboard);
 						if will_promote {
 							for promotion in KNIGHT..=QUEEN {
-								result.push(
+								moves.push(
 									MoveData {
 										flag: promotion as u8,
 										capture: self.get_piece(capture_index) as u8,
@@ -691,7 +822,7 @@ impl Board {
 								);
 							}
 						} else {
-							result.push(
+							moves.push(
 								MoveData {
 									flag: 0,
 									capture: self.get_piece(capture_index) as u8,
@@ -708,7 +839,7 @@ impl Board {
 					if last_move.flag == DOUBLE_PAWN_PUSH_FLAG {
 						if piece_index % 8 != 0
 						&& last_move.to == piece_index - 1 { // Left
-							result.push(
+							moves.push(
 								MoveData {
 									flag: EN_PASSANT_FLAG,
 									capture: self.get_piece(piece_index - 1) as u8,
@@ -719,7 +850,7 @@ impl Board {
 							);
 						} else if piece_index % 8 != 7
 						&& last_move.to == piece_index + 1 { // Right
-							result.push(
+							moves.push(
 								MoveData {
 									flag: EN_PASSANT_FLAG,
 									capture: self.get_piece(piece_index + 1) as u8,
@@ -740,13 +871,15 @@ impl Board {
 					   self.precalculated_move_data.knight_attacks[piece_index as usize]
 					& !self.color_bitboards[piece_is_white as usize];
 
-				if only_captures {
-					bitboard &= self.piece_bitboards[!piece_is_white as usize];
+				match gen_type {
+					MoveGenType::Captures => bitboard &= self.color_bitboards[!piece_is_white as usize],
+					MoveGenType::Quiets => bitboard &= self.unoccupied_bitboard(),
+					MoveGenType::All => {}
 				}
 
 				while bitboard != 0 {
 					let to = pop_lsb(&mut bitboard);
-					result.push(
+					moves.push(
 						MoveData {
 							flag: 0,
 							capture: self.get_piece(to) as u8,
@@ -763,13 +896,15 @@ impl Board {
 					   self.calculate_bishop_attack_bitboard(piece_index as usize)
 					& !self.color_bitboards[piece_is_white as usize];
 
-				if only_captures {
-					moves_bitboard &= self.piece_bitboards[!piece_is_white as usize];
+				match gen_type {
+					MoveGenType::Captures => moves_bitboard &= self.color_bitboards[!piece_is_white as usize],
+					MoveGenType::Quiets => moves_bitboard &= self.unoccupied_bitboard(),
+					MoveGenType::All => {}
 				}
 
 				while moves_bitboard != 0 {
 					let to = pop_lsb(&mut moves_bitboard);
-					result.push(
+					moves.push(
 						MoveData {
 							flag: 0,
 							capture: self.get_piece(to) as u8,
@@ -786,13 +921,15 @@ impl Board {
 					   self.calculate_rook_attack_bitboard(piece_index as usize)
 					& !self.color_bitboards[piece_is_white as usize];
 
-				if only_captures {
-					moves_bitboard &= self.piece_bitboards[!piece_is_white as usize];
+				match gen_type {
+					MoveGenType::Captures => moves_bitboard &= self.color_bitboards[!piece_is_white as usize],
+					MoveGenType::Quiets => moves_bitboard &= self.unoccupied_bitboard(),
+					MoveGenType::All => {}
 				}
 
 				while moves_bitboard != 0 {
 					let to = pop_lsb(&mut moves_bitboard);
-					result.push(
+					moves.push(
 						MoveData {
 							flag: 0,
 							capture: self.get_piece(to) as u8,
@@ -809,13 +946,15 @@ impl Board {
 					   self.calculate_queen_attack_bitboard(piece_index as usize)
 					& !self.color_bitboards[piece_is_white as usize];
 
-				if only_captures {
-					moves_bitboard &= self.piece_bitboards[!piece_is_white as usize];
+				match gen_type {
+					MoveGenType::Captures => moves_bitboard &= self.color_bitboards[!piece_is_white as usize],
+					MoveGenType::Quiets => moves_bitboard &= self.unoccupied_bitboard(),
+					MoveGenType::All => {}
 				}
 
 				while moves_bitboard != 0 {
 					let to = pop_lsb(&mut moves_bitboard);
-					result.push(
+					moves.push(
 						MoveData {
 							flag: 0,
 							capture: self.get_piece(to) as u8,
@@ -832,11 +971,15 @@ impl Board {
 					   self.precalculated_move_data.king_attacks[piece_index as usize]
 					& !self.color_bitboards[piece_is_white as usize];
 
-				if only_captures {
-					bitboard &= self.piece_bitboards[!piece_is_white as usize];
-				} else {
+				match gen_type {
+					MoveGenType::Captures => bitboard &= self.color_bitboards[!piece_is_white as usize],
+					MoveGenType::Quiets => bitboard &= self.unoccupied_bitboard(),
+					MoveGenType::All => {}
+				}
+
+				if gen_type != MoveGenType::Captures {
 					if self.can_short_castle(piece_is_white) {
-						result.push(
+						moves.push(
 							MoveData {
 								flag: SHORT_CASTLE_FLAG,
 								capture: NO_PIECE as u8,
@@ -848,7 +991,7 @@ impl Board {
 					}
 
 					if self.can_long_castle(piece_is_white) {
-						result.push(
+						moves.push(
 							MoveData {
 								flag: LONG_CASTLE_FLAG,
 								capture: NO_PIECE as u8,
@@ -862,7 +1005,7 @@ impl Board {
 
 				while bitboard != 0 {
 					let to = pop_lsb(&mut bitboard);
-					result.push(
+					moves.push(
 						MoveData {
 							flag: 0,
 							capture: self.get_piece(to) as u8,
@@ -876,139 +1019,341 @@ impl Board {
 
 			_ => {}
 		}
-
-		result
 	}
 
-	fn calculate_bishop_attack_bitboard(&self, piece_index: usize) -> u64 {
-		let relevant_occupied_squares =
-			self.occupied_bitboard()
-			& self.precalculated_move_data.bishop_relevant_occupancy_masks[piece_index];
-		let key = self.precalculated_move_data.generate_bishop_key(piece_index, relevant_occupied_squares);
-		self.precalculated_move_data.bishop_attacks[piece_index][key]
-	}
+	// Checks a move that didn't come from this position's move generation (hash, killer and counter moves),
+	// only using the flag, start and end squares, so that it's safe to make without generating every move
+	pub fn is_pseudo_legal(&mut self, data: MoveData) -> bool {
+		if data == NULL_MOVE
+		|| data.from == data.to
+		|| data.from >= 64
+		|| data.to >= 64 {
+			return false;
+		}
 
-	fn calculate_rook_attack_bitboard(&self, piece_index: usize) -> u64 {
-		let relevant_occupied_squares =
-			self.occupied_bitboard()
-			& self.precalculated_move_data.rook_relevant_occupancy_masks[piece_index];
-		let key = self.precalculated_move_data.generate_rook_key(piece_index, relevant_occupied_squares);
-		self.precalculated_move_data.rook_attacks[piece_index][key]
+		let piece = self.get_piece(data.from);
+		if piece == NO_PIECE
+		|| is_piece_white(piece) != self.white_to_move {
+			return false;
+		}
+
+		let piece_is_white = is_piece_white(piece);
+		let capture = self.get_piece(data.to);
+		if capture != NO_PIECE
+		&& is_piece_white(capture) == piece_is_white {
+			return false;
+		}
+
+		let from = data.from as usize;
+		let to = 1 << data.to;
+
+		match get_piece_type(piece) {
+			PAWN => {
+				let forward: i8 = if piece_is_white { -8 } else { 8 };
+				let start_rank = if piece_is_white { 6 } else { 1 };
+				let promotion_rank = if piece_is_white { 0 } else { 7 };
+
+				let single_push = data.from as i8 + forward;
+				let will_promote = data.to / 8 == promotion_rank;
+
+				match data.flag {
+					DOUBLE_PAWN_PUSH_FLAG =>
+						   data.from / 8 == start_rank
+						&& data.to as i8 == single_push + forward
+						&& self.square_is_empty(single_push as u8)
+						&& self.square_is_empty(data.to),
+
+					EN_PASSANT_FLAG => {
+						let last_move = self.get_last_move();
+						   last_move.flag == DOUBLE_PAWN_PUSH_FLAG
+						&& last_move.to / 8 == data.from / 8
+						&& (last_move.to as i8 - data.from as i8).abs() == 1
+						&& data.to as i8 == last_move.to as i8 + forward
+					}
+
+					// 1, 2, 3, 4 for promoting pieces
+					0..=4 => {
+						if (data.flag != 0) != will_promote {
+							return false;
+						}
+
+						if capture == NO_PIECE {
+							data.to as i8 == single_push
+						} else {
+							self.precalculated_move_data.pawn_attacks[piece_is_white as usize][from] & to != 0
+						}
+					}
+
+					_ => false,
+				}
+			}
+
+			KING => {
+				match data.flag {
+					0 => self.precalculated_move_data.king_attacks[from] & to != 0,
+					SHORT_CASTLE_FLAG => data.to == data.from + 2 && self.can_short_castle(piece_is_white),
+					LONG_CASTLE_FLAG => data.from >= 2 && data.to == data.from - 2 && self.can_long_castle(piece_is_white),
+					_ => false,
+				}
+			}
+
+			piece_type => {
+				if data.flag != 0 {
+					return false;
+				}
+
+				(match piece_type {
+					KNIGHT => self.precalculated_move_data.knight_attacks[from],
+					BISHOP => self.calculate_bishop_attack_bitboard(from),
+					ROOK   => self.calculate_rook_attack_bitboard(from),
+					_      => self.calculate_queen_attack_bitboard(from),
+				}) & to != 0
+			}
+		}
 	}
 
-	fn calculate_queen_attack_bitboard(&self, piece_index: usize) -> u64 {
-		  self.calculate_bishop_attack_bitboard(piece_index)
-		| self.calculate_rook_attack_bitboard(piece_index)
+	// Fills in the moving and captured pieces of a move that only has a flag, start and end square
+	pub fn complete_move_data(&self, data: MoveData) -> MoveData {
+		let piece = self.get_piece(data.from);
+
+		let capture =
+			if data.flag == EN_PASSANT_FLAG {
+				build_piece(!is_piece_white(piece), PAWN)
+			} else {
+				self.get_piece(data.to)
+			};
+
+		MoveData {
+			flag: data.flag,
+			capture: capture as u8,
+			piece: piece as u8,
+			from: data.from,
+			to: data.to,
+		}
 	}
 
-	// Returns a value between 0.0 and 1.0 to reflect whether you're in an endgame or not
-	// the closer to 1.0, the more of an endgame it is
-	pub fn endgame_multiplier(&self) -> f32 {
-		(1.5 - self.total_material_without_pawns.iter().sum::<i32>() as f32 * (0.9 / MAX_ENDGAME_MATERIAL)).clamp(0.0, 1.0)
-		// (1.0 - self.total_material_without_pawns as f32 * (1.0 / MAX_ENDGAME_MATERIAL)).clamp(0.0, 1.0)
+	// Only generates quiet moves that give check, either directly or by uncovering an attack from a sliding piece
+	pub fn get_quiet_checks_for_color(&mut self, white_pieces: bool, moves: &mut MoveList) {
+		let mut quiets = MoveList::new();
+		self.get_pseudo_legal_moves_for_color(white_pieces, MoveGenType::Quiets, &mut quiets);
+
+		for m in quiets.iter().copied() {
+			if self.gives_check(m) {
+				moves.push(m);
+			}
+		}
 	}
 
-	pub fn perspective(&self) -> i32 { if self.white_to_move { 1 } else { -1 } }
+	// Checks whether a pseudo-legal move would put the enemy king in check, without making the move
+	pub fn gives_check(&self, data: MoveData) -> bool {
+		let piece_is_white = is_piece_white(data.piece as usize);
+		let enemy_king = self.piece_bitboards[build_piece(!piece_is_white, KING)];
+		if enemy_king == 0 {
+			return false;
+		}
 
-	pub fn hc_evaluate(&mut self) -> i32 {
-		let endgame = self.endgame_multiplier();
+		let enemy_king_index = get_lsb(enemy_king) as usize;
+		let from = 1 << data.from;
+		let to = 1 << data.to;
 
-		let mut white_material = 0;
-		let mut black_material = 0;
+		let mut occupied = (self.occupied_bitboard() ^ from) | to;
 
-		let mut white_pawn_evaluation = 0;
-		let mut black_pawn_evaluation = 0;
+		if data.flag == EN_PASSANT_FLAG {
+			let en_passant_square =
+				if piece_is_white {
+					data.to + 8
+				} else {
+					data.to - 8
+				};
 
-		for piece in 0..PIECE_COUNT {
-			let piece_is_white = is_piece_white(piece);
-			let piece_type = get_piece_type(piece);
+			occupied ^= 1 << en_passant_square;
+		}
 
-			let mut bitboard = self.piece_bitboards[piece];
-			while bitboard != 0 {
-				let piece_index = pop_lsb(&mut bitboard) as usize;
+		let piece_type =
+			if PROMOTABLE.contains(&data.flag) {
+				data.flag as usize
+			} else {
+				get_piece_type(data.piece as usize)
+			};
 
-				if piece_is_white {
-					white_material += get_full_worth_of_piece(piece, piece_index, endgame);
+		// Direct checks
+		let attacks = match piece_type {
+			PAWN   => self.precalculated_move_data.pawn_attacks[piece_is_white as usize][data.to as usize],
+			KNIGHT => self.precalculated_move_data.knight_attacks[data.to as usize],
+			BISHOP => self.calculate_bishop_attack_bitboard_with_occupancy(data.to as usize, occupied),
+			ROOK   => self.calculate_rook_attack_bitboard_with_occupancy(data.to as usize, occupied),
+			QUEEN  => self.calculate_bishop_attack_bitboard_with_occupancy(data.to as usize, occupied)
+			        | self.calculate_rook_attack_bitboard_with_occupancy(data.to as usize, occupied),
+			_ => 0,
+		};
 
-					if piece_type == PAWN {
-						if self.precalculated_move_data.file_of_square[piece_index] & self.piece_bitboards[WHITE_PAWN] != 0 { // Doubled pawn
-							white_pawn_evaluation -= DOUBLED_PAWN_PENALTY;
-						}
+		if attacks & enemy_king != 0 {
+			return true;
+		}
 
-						if self.precalculated_move_data.files_beside_square[piece_index] & self.piece_bitboards[WHITE_PAWN] == 0 { // Isolated pawn
-							white_pawn_evaluation -= ISOLATED_PAWN_PENALTY;
-						}
+		// The rook can give check after castling
+		if data.flag == SHORT_CASTLE_FLAG
+		|| data.flag == LONG_CASTLE_FLAG {
+			let (rook_from, rook_to) =
+				if data.flag == SHORT_CASTLE_FLAG {
+					(data.to + 1, data.to - 1)
+				} else {
+					(data.to - 2, data.to + 1)
+				};
 
-						if self.precalculated_move_data.squares_ahead_of_pawn[1][piece_index] & self.piece_bitboards[BLACK_PAWN] == 0
-						&& self.precalculated_move_data.file_in_front_of_pawn[1][piece_index] & self.piece_bitboards[WHITE_PAWN] == 0 { // Passed pawn
-							white_pawn_evaluation += PASSED_PAWN_BOOST[7 - piece_index / 8];
-						}
-					}
+			occupied = (occupied ^ (1 << rook_from)) | (1 << rook_to);
+
+			if self.calculate_rook_attack_bitboard_with_occupancy(rook_to as usize, occupied) & enemy_king != 0 {
+				return true;
+			}
+		}
+
+		// Discovered checks
+		let own_queens = self.piece_bitboards[build_piece(piece_is_white, QUEEN)];
+		let own_diagonal_sliders = (self.piece_bitboards[build_piece(piece_is_white, BISHOP)] | own_queens) & !from & occupied;
+		let own_orthogonal_sliders = (self.piece_bitboards[build_piece(piece_is_white, ROOK)] | own_queens) & !from & occupied;
+
+		   self.calculate_bishop_attack_bitboard_with_occupancy(enemy_king_index, occupied) & own_diagonal_sliders != 0
+		|| self.calculate_rook_attack_bitboard_with_occupancy(enemy_king_index, occupied) & own_orthogonal_sliders != 0
+	}
+
+	// Pieces of both colors that attack a square, with sliders seeing through everything that isn't in `occupied`
+	pub fn attackers_of_square(&self, square: usize, occupied: u64) -> u64 {
+		let diagonal_sliders =
+			  self.piece_bitboards[WHITE_BISHOP] | self.piece_bitboards[BLACK_BISHOP]
+			| self.piece_bitboards[WHITE_QUEEN] | self.piece_bitboards[BLACK_QUEEN];
+		let orthogonal_sliders =
+			  self.piece_bitboards[WHITE_ROOK] | self.piece_bitboards[BLACK_ROOK]
+			| self.piece_bitboards[WHITE_QUEEN] | self.piece_bitboards[BLACK_QUEEN];
+
+		(
+			  (self.precalculated_move_data.pawn_attacks[0][square] & self.piece_bitboards[WHITE_PAWN])
+			| (self.precalculated_move_data.pawn_attacks[1][square] & self.piece_bitboards[BLACK_PAWN])
+			| (self.precalculated_move_data.knight_attacks[square] & (self.piece_bitboards[WHITE_KNIGHT] | self.piece_bitboards[BLACK_KNIGHT]))
+			| (self.precalculated_move_data.king_attacks[square] & (self.piece_bitboards[WHITE_KING] | self.piece_bitboards[BLACK_KING]))
+			| (self.calculate_bishop_attack_bitboard_with_occupancy(square, occupied) & diagonal_sliders)
+			| (self.calculate_rook_attack_bitboard_with_occupancy(square, occupied) & orthogonal_sliders)
+		) & occupied
+	}
+
+	// Static exchange evaluation: how much material a capture wins if both sides keep recapturing on
+	// that square with their cheapest piece, and either side can stop whenever it's ahead. Pins are ignored
+	pub fn static_exchange_evaluation(&self, data: MoveData) -> i32 {
+		let to = data.to as usize;
+		let mut occupied = self.occupied_bitboard() ^ (1 << data.from);
+
+		// gains[n] is what the side making the nth capture wins, if the other side doesn't recapture
+		let mut gains = [0; 32];
+		let mut depth = 0;
+
+		if data.capture != NO_PIECE as u8 {
+			gains[0] = BASE_WORTHS_OF_PIECE_TYPE[get_piece_type(data.capture as usize)];
+		}
+
+		if data.flag == EN_PASSANT_FLAG {
+			let en_passant_square =
+				if is_piece_white(data.piece as usize) {
+					data.to + 8
 				} else {
-					black_material += get_full_worth_of_piece(piece, piece_index, endgame);
+					data.to - 8
+				};
 
-					if piece_type == PAWN {
-						if self.precalculated_move_data.file_of_square[piece_index] & self.piece_bitboards[BLACK_PAWN] != 0 { // Doubled pawn
-							black_pawn_evaluation -= DOUBLED_PAWN_PENALTY;
-						}
+			occupied ^= 1 << en_passant_square;
+		}
 
-						if self.precalculated_move_data.files_beside_square[piece_index] & self.piece_bitboards[BLACK_PAWN] == 0 { // Isolated pawn
-							black_pawn_evaluation -= ISOLATED_PAWN_PENALTY;
-						}
+		// The piece that's standing on the square, and that the other side can take next
+		let mut target_worth =
+			if PROMOTABLE.contains(&data.flag) {
+				gains[0] += BASE_WORTHS_OF_PIECE_TYPE[data.flag as usize] - BASE_WORTHS_OF_PIECE_TYPE[PAWN];
+				BASE_WORTHS_OF_PIECE_TYPE[data.flag as usize]
+			} else {
+				BASE_WORTHS_OF_PIECE_TYPE[get_piece_type(data.piece as usize)]
+			};
 
-						if self.precalculated_move_data.squares_ahead_of_pawn[0][piece_index] & self.piece_bitboards[WHITE_PAWN] == 0
-						&& self.precalculated_move_data.file_in_front_of_pawn[0][piece_index] & self.piece_bitboards[BLACK_PAWN] == 0 { // Passed pawn
-							black_pawn_evaluation += PASSED_PAWN_BOOST[piece_index / 8];
-						}
-					}
+		let mut white = !is_piece_white(data.piece as usize);
+
+		loop {
+			let attackers = self.attackers_of_square(to, occupied);
+			let own_attackers = attackers & self.color_bitboards[white as usize];
+			if own_attackers == 0 {
+				break;
+			}
+
+			let mut attacker_type = PAWN;
+			let mut attacker = 0;
+			for piece_type in PAWN..=KING {
+				let bitboard = own_attackers & self.piece_bitboards[build_piece(white, piece_type)];
+				if bitboard != 0 {
+					attacker_type = piece_type;
+					attacker = bitboard & bitboard.wrapping_neg();
+					break;
 				}
 			}
-		}
 
-		let pawn_evaluation_multiplier = (endgame + 0.3).clamp(0.3, 1.0); // TODO
-		white_pawn_evaluation = (white_pawn_evaluation as f32 * pawn_evaluation_multiplier) as i32;
-		black_pawn_evaluation = (black_pawn_evaluation as f32 * pawn_evaluation_multiplier) as i32;
+			// The king can only take if the square isn't defended anymore
+			if attacker_type == KING
+			&& attackers & self.color_bitboards[!white as usize] != 0 {
+				break;
+			}
 
-		let white_attacks_bitboard = self.get_attacked_squares_for_color(1);
-		let black_attacks_bitboard = self.get_attacked_squares_for_color(0);
+			depth += 1;
+			gains[depth] = target_worth - gains[depth - 1];
+			target_worth = BASE_WORTHS_OF_PIECE_TYPE[attacker_type];
 
-		// Taking the sqrt of this made it worse
-		let white_attacks_score = white_attacks_bitboard.count_ones() as i32 * 10;
-		let black_attacks_score = black_attacks_bitboard.count_ones() as i32 * 10;
+			occupied ^= attacker;
+			white = !white;
+		}
 
-		let white_king_index = get_lsb(self.piece_bitboards[WHITE_KING]) as usize;
-		let black_king_index = get_lsb(self.piece_bitboards[BLACK_KING]) as usize;
+		// Going back up, each side picks between recapturing and stopping
+		while depth > 0 {
+			gains[depth - 1] = -i32::max(-gains[depth - 1], gains[depth]);
+			depth -= 1;
+		}
 
-		// TODO: weak squares, weak lines, or none?
-		// TODO: Or count how many friendly pieces are around the king?
-		let white_king_weakness_penalty = ((
-				  self.precalculated_move_data.king_attacks[white_king_index]
-				& black_attacks_bitboard
-			).count_ones() as f32 * (1.0 - endgame)) as i32 * 20;
+		gains[0]
+	}
 
-		let black_king_weakness_penalty = ((
-				  self.precalculated_move_data.king_attacks[black_king_index]
-				& white_attacks_bitboard
-			).count_ones() as f32 * (1.0 - endgame)) as i32 * 20;
+	pub fn calculate_bishop_attack_bitboard(&self, piece_index: usize) -> u64 {
+		self.calculate_bishop_attack_bitboard_with_occupancy(piece_index, self.occupied_bitboard())
+	}
 
-		// let weak_lines_from_white_king = (self.calculate_queen_attack_bitboard(white_king_index).count_ones() as f32 * (1.0 - endgame)) as i32;
-		// let weak_lines_from_black_king = (self.calculate_queen_attack_bitboard(black_king_index).count_ones() as f32 * (1.0 - endgame)) as i32;
+	pub fn calculate_rook_attack_bitboard(&self, piece_index: usize) -> u64 {
+		self.calculate_rook_attack_bitboard_with_occupancy(piece_index, self.occupied_bitboard())
+	}
 
-		// TODO: a small boost for having the bishop pair?
+	// These are for looking at the board as if a move had been made, without actually making it
+	pub fn calculate_bishop_attack_bitboard_with_occupancy(&self, piece_index: usize, occupied: u64) -> u64 {
+		let relevant_occupied_squares =
+			occupied
+			& self.precalculated_move_data.bishop_relevant_occupancy_masks[piece_index];
+		let key = self.precalculated_move_data.generate_bishop_key(piece_index, relevant_occupied_squares);
+		self.precalculated_move_data.bishop_attacks[piece_index][key]
+	}
 
-		// TODO: rooks on open lines
+	pub fn calculate_rook_attack_bitboard_with_occupancy(&self, piece_index: usize, occupied: u64) -> u64 {
+		let relevant_occupied_squares =
+			occupied
+			& self.precalculated_move_data.rook_relevant_occupancy_masks[piece_index];
+		let key = self.precalculated_move_data.generate_rook_key(piece_index, relevant_occupied_squares);
+		self.precalculated_move_data.rook_attacks[piece_index][key]
+	}
 
-		 ((white_material + white_attacks_score - white_king_weakness_penalty + white_pawn_evaluation)
-		- (black_material + black_attacks_score - black_king_weakness_penalty + black_pawn_evaluation)) * self.perspective()
+	pub fn calculate_queen_attack_bitboard(&self, piece_index: usize) -> u64 {
+		  self.calculate_bishop_attack_bitboard(piece_index)
+		| self.calculate_rook_attack_bitboard(piece_index)
 	}
 
-	// pub fn raw_nnue_evaluate(&self) -> f32 {
-	// 	self.nnue.evaluate(self.occupied_bitboard().count_ones() as usize)
-	// }
+	pub fn perspective(&self) -> i32 { if self.white_to_move { 1 } else { -1 } }
 
-	// pub fn nnue_evaluate(&self) -> i32 {
-	// 	(self.nnue.evaluate(self.occupied_bitboard().count_ones() as usize) * NNUE_EVAL_SCALE) as i32 * self.perspective()
-	// }
+	// Call set_nnue(true) first, the accumulator isn't kept up to date otherwise
+	pub fn raw_nnue_evaluate(&self) -> f32 {
+		debug_assert!(self.nnue_enabled, "Evaluating with the NNUE turned off");
+		self.nnue.evaluate()
+	}
+
+	pub fn nnue_evaluate(&self) -> i32 {
+		(self.raw_nnue_evaluate() * NNUE_EVAL_SCALE) as i32 * self.perspective()
+	}
 
 	pub fn can_short_castle(&mut self, white: bool) -> bool {
 		// self.king_in_check calculates attacked squares
@@ -1049,7 +1394,7 @@ impl Board {
 
 	pub fn undo_null_move(&mut self) {
 		self.white_to_move = !self.white_to_move;
-		self.zobrist.key.pop();
+		self.zobrist.undo_move();
 		self.moves.pop();
 
 		self.board_state.pop();
@@ -1062,7 +1407,8 @@ impl Board {
 			return false;
 		}
 
-		let lookback = self.zobrist.key.index - self.board_state.current.fifty_move_counter as usize;
+		// The fifty move counter from a FEN can be higher than the number of moves made since
+		let lookback = self.zobrist.key.index.saturating_sub(self.board_state.current.fifty_move_counter as usize);
 		let mut i = self.zobrist.key.index - 2;
 
 		while i >= lookback {
@@ -0,0 +1,123 @@
+/*
+Book learning: every book move Maxwell plays is remembered until the game's result comes in,
+and then the result is saved for that move, so lines that keep losing get played less and less, and winning lines more
+
+The learning file sits next to the book (<book file>.learn, or maxwell_book.learn for the built in lines),
+and has one move per line:
+	<polyglot key in hex> <move coordinates> <games> <points>
+*/
+
+use std::collections::HashMap;
+use std::io::Write;
+use crate::move_data::MoveData;
+use crate::polyglot::polyglot_key;
+use crate::Board;
+
+pub const BUILT_IN_LEARNING_FILE: &str = "maxwell_book.learn";
+
+#[derive(Copy, Clone, Default)]
+pub struct LearnedMove {
+	pub games: u32,
+	// From the perspective of the side that played the move
+	pub points: f32,
+}
+
+impl LearnedMove {
+	// 1.0 for a move that hasn't been played yet, and it drops fast towards 0.0 for moves that lose (or rises up to 4.0 for ones that win),
+	// the +1 and +2 make sure one unlucky game doesn't throw a move out of the book completely
+	pub fn weight_factor(&self) -> f32 {
+		let score = (self.points + 1.0) / (self.games as f32 + 2.0);
+		(score * 2.0).powi(2)
+	}
+}
+
+pub struct BookLearning {
+	pub path: String,
+	pub moves: HashMap<(u64, String), LearnedMove>,
+	// Book moves played in the current game: key, move, and if white played it
+	pub pending: Vec<(u64, MoveData, bool)>,
+}
+
+impl BookLearning {
+	pub fn open(book_file: &str) -> Self {
+		let path =
+			if book_file.is_empty()
+			|| book_file == "<empty>" {
+				BUILT_IN_LEARNING_FILE.to_string()
+			} else {
+				format!("{}.learn", book_file)
+			};
+
+		let mut learning = Self {
+			path,
+			moves: HashMap::new(),
+			pending: vec![],
+		};
+
+		// There's no file until the first game finishes
+		if let Ok(text) = std::fs::read_to_string(&learning.path) {
+			for line in text.lines() {
+				let split = line.trim().split(' ').collect::<Vec<&str>>();
+				if split.len() != 4
+				|| split[0].starts_with('#') {
+					continue;
+				}
+
+				if let (Ok(key), Ok(games), Ok(points)) = (u64::from_str_radix(split[0], 16), split[2].parse::<u32>(), split[3].parse::<f32>()) {
+					learning.moves.insert((key, split[1].to_string()), LearnedMove { games, points });
+				}
+			}
+		}
+
+		learning
+	}
+
+	// Scales the book's weights by what was learned. Everything is multiplied by 100 first, so the weights of
+	// tiny books (like the built in one, where every move is 1) can still go down
+	pub fn adjust_weights(&self, board: &Board, moves: &mut [(MoveData, u32)]) {
+		let key = polyglot_key(board);
+
+		for (data, weight) in moves.iter_mut() {
+			let factor = self.moves.get(&(key, data.to_coordinates()))
+				.map(|learned| learned.weight_factor())
+				.unwrap_or(1.0);
+
+			*weight = u32::max(1, (*weight as f32 * 100.0 * factor).round() as u32);
+		}
+	}
+
+	pub fn book_move_played(&mut self, board: &Board, data: MoveData) {
+		self.pending.push((polyglot_key(board), data, board.white_to_move));
+	}
+
+	// result is from white's perspective: 1.0 = white won, 0.5 = draw, 0.0 = black won
+	pub fn learn(&mut self, result: f32) {
+		if self.pending.is_empty() {
+			return;
+		}
+
+		for (key, data, white) in self.pending.drain(..) {
+			let learned = self.moves.entry((key, data.to_coordinates())).or_default();
+			learned.games += 1;
+			learned.points += if white { result } else { 1.0 - result };
+		}
+
+		if let Err(error) = self.save() {
+			println!("info string Failed to save book learning to {}: {}", self.path, error);
+		}
+	}
+
+	fn save(&self) -> std::io::Result<()> {
+		let mut moves = self.moves.iter().collect::<Vec<_>>();
+		moves.sort_by(|a, b| a.0.cmp(b.0));
+
+		let mut file = std::io::BufWriter::new(std::fs::File::create(&self.path)?);
+		writeln!(file, "# Maxwell book learning: <polyglot key> <move> <games> <points>")?;
+
+		for ((key, coordinates), learned) in moves {
+			writeln!(file, "{:016x} {} {} {}", key, coordinates, learned.games, learned.points)?;
+		}
+
+		file.flush()
+	}
+}
@@ -0,0 +1,172 @@
+/*
+Builds opening books out of PGN collections
+
+Every game is replayed up to max_ply, and every move counts how many times it was played from its position,
+and how many points the side that played it scored. Moves that weren't played enough, or scored too badly, are thrown out,
+and the rest are weighted by their score rate
+
+Books ending in .bin are written in the Polyglot format, anything else in Maxwell's own text format (see OpeningBook)
+*/
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::Instant;
+use crate::move_data::MoveData;
+use crate::polyglot::polyglot_key;
+use crate::polyglot_book::{BookEntry, encode_move, write_book};
+use crate::pgn::{read_games, san_to_move_data};
+use crate::{Board, STARTING_FEN};
+
+pub const DEFAULT_MAX_PLY: usize = 20;
+pub const DEFAULT_MIN_GAMES: u32 = 3;
+
+pub struct BookMakerConfig {
+	pub output: String,
+	pub pgn_files: Vec<String>,
+	pub max_ply: usize,
+	pub min_games: u32,
+	// Percent, from the perspective of the side that played the move
+	pub min_score: f32,
+}
+
+impl BookMakerConfig {
+	// makebook <output> <pgn files...> (max_ply=20) (min_games=3) (min_score=0)
+	pub fn from_args(args: &[&str]) -> Option<Self> {
+		let mut config = Self {
+			output: args.first()?.to_string(),
+			pgn_files: vec![],
+			max_ply: DEFAULT_MAX_PLY,
+			min_games: DEFAULT_MIN_GAMES,
+			min_score: 0.0,
+		};
+
+		for arg in args.iter().skip(1) {
+			match arg.split_once('=') {
+				Some(("max_ply", value)) => config.max_ply = value.parse().unwrap_or(DEFAULT_MAX_PLY),
+				Some(("min_games", value)) => config.min_games = value.parse().unwrap_or(DEFAULT_MIN_GAMES),
+				Some(("min_score", value)) => config.min_score = value.parse().unwrap_or(0.0),
+				_ => config.pgn_files.push(arg.to_string()),
+			}
+		}
+
+		if config.pgn_files.is_empty() {
+			return None;
+		}
+
+		Some(config)
+	}
+}
+
+#[derive(Copy, Clone)]
+pub struct MoveStats {
+	pub data: MoveData,
+	pub games: u32,
+	pub points: f32,
+}
+
+impl MoveStats {
+	pub fn score(&self) -> f32 { self.points / self.games as f32 }
+
+	// Polyglot weights are 16 bits, and a move that always loses still gets a tiny chance
+	pub fn weight(&self) -> u16 { u16::max(1, (self.score() * 1000.0).round() as u16) }
+}
+
+pub fn make_book(config: &BookMakerConfig) {
+	let timer = Instant::now();
+	let mut positions: HashMap<u64, Vec<MoveStats>> = HashMap::new();
+
+	let mut used_games = 0;
+	let mut skipped_games = 0;
+
+	let mut board = Board::from_fen(STARTING_FEN);
+
+	for path in config.pgn_files.iter() {
+		// Lots of PGNs out there aren't UTF-8
+		let Ok(bytes) = std::fs::read(path) else {
+			println!("Failed to read {}", path);
+			continue;
+		};
+
+		for game in read_games(&String::from_utf8_lossy(&bytes)) {
+			// Unfinished games don't say anything about the moves, and games from other positions aren't useful for a book
+			let Some(result) = game.result else {
+				skipped_games += 1;
+				continue;
+			};
+			if game.fen.is_some() {
+				skipped_games += 1;
+				continue;
+			}
+
+			used_games += 1;
+
+			for san in game.moves.iter().take(config.max_ply) {
+				let Some(data) = san_to_move_data(&mut board, san) else {
+					break;
+				};
+
+				let points = if board.white_to_move { result } else { 1.0 - result };
+				let moves = positions.entry(polyglot_key(&board)).or_default();
+
+				if let Some(stats) = moves.iter_mut().find(|stats| stats.data == data) {
+					stats.games += 1;
+					stats.points += points;
+				} else {
+					moves.push(MoveStats { data, games: 1, points });
+				}
+
+				board.make_move(data);
+			}
+
+			while board.undo_last_move() {}
+		}
+	}
+
+	let mut entries = vec![];
+	for (key, moves) in positions.iter() {
+		for stats in moves.iter() {
+			if stats.games >= config.min_games
+			&& stats.score() * 100.0 >= config.min_score {
+				entries.push(BookEntry {
+					key: *key,
+					raw_move: encode_move(stats.data),
+					weight: stats.weight(),
+					learn: 0,
+				});
+			}
+		}
+	}
+
+	let result =
+		if config.output.ends_with(".bin") {
+			write_book(&config.output, &mut entries)
+		} else {
+			write_native_book(&config.output, &mut entries)
+		};
+
+	if let Err(error) = result {
+		println!("Failed to write {}: {}", config.output, error);
+		return;
+	}
+
+	println!("Read {} games ({} skipped), wrote {} moves to {} in {} seconds",
+		used_games,
+		skipped_games,
+		entries.len(),
+		config.output,
+		timer.elapsed().as_secs_f32(),
+	);
+}
+
+fn write_native_book(path: &str, entries: &mut [BookEntry]) -> std::io::Result<()> {
+	entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)));
+
+	let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+	writeln!(file, "# Maxwell opening book: <polyglot key> <move> <weight>")?;
+
+	for entry in entries.iter() {
+		writeln!(file, "{:016x} {} {}", entry.key, entry.move_coordinates(), entry.weight)?;
+	}
+
+	file.flush()
+}
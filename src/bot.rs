@@ -3,10 +3,21 @@ use crate::piece_square_tables::{PAWN_WORTH, QUEEN_WORTH};
 use crate::pieces::{PAWN, PROMOTABLE, NO_PIECE};
 use crate::utils::{CHECKMATE_EVAL, evaluation_is_mate, ply_from_mate};
 use std::time::Instant;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 use crate::move_sorter::MoveSorter;
+use crate::move_picker::MovePicker;
+use crate::board::MoveGenType;
+use crate::move_list::MoveList;
 use crate::transposition_table::{TranspositionTable, EvalBound};
 use crate::move_data::{MoveData, NULL_MOVE};
 use crate::opening_book::OpeningBook;
+use crate::polyglot_book::{PolyglotBook, choose_book_move};
+use crate::book_learning::BookLearning;
+use crate::experience::{Experience, DEFAULT_EXPERIENCE_SIZE};
+use crate::eval_params::EvalParams;
+use crate::syzygy::{Syzygy, TB_WIN_EVAL, WDL_WIN, WDL_LOSS};
+use crate::endgame_tablebase::EndgameTablebases;
 use crate::Board;
 
 pub const MAX_DEPTH: u8 = 128;
@@ -17,8 +28,18 @@ pub struct BotConfig {
 	pub fen: String,
 	pub debug_output: bool,
 	pub opening_book: bool,
+	pub book_file: String,
+	pub book_best_move: bool,
+	pub book_learning: bool,
 	pub time_management: bool,
 	pub hash_size: usize,
+	pub qsearch_checks: bool,
+	pub syzygy_path: String,
+	pub tablebase_path: String,
+	pub experience_file: String,
+	pub experience_size: usize,
+	pub eval_file: String,
+	pub nnue: bool,
 }
 
 impl BotConfig {
@@ -30,8 +51,18 @@ impl BotConfig {
 			fen: Self::get_arg_value(&args, "fen").unwrap_or(STARTING_FEN.to_string()),
 			debug_output: Self::get_arg_value(&args, "debug_output").unwrap_or(_true.clone()) == _true,
 			opening_book: Self::get_arg_value(&args, "opening_book").unwrap_or(_false.clone()) == _true,
+			book_file: Self::get_arg_value(&args, "book_file").unwrap_or_default(),
+			book_best_move: Self::get_arg_value(&args, "book_best_move").unwrap_or(_false.clone()) == _true,
+			book_learning: Self::get_arg_value(&args, "book_learning").unwrap_or(_true.clone()) == _true,
 			time_management: Self::get_arg_value(&args, "time_management").unwrap_or(_true.clone()) == _true,
 			hash_size: (Self::get_arg_value(&args, "hash_size").unwrap_or("256".to_string())).parse::<usize>().unwrap_or(256),
+			qsearch_checks: Self::get_arg_value(&args, "qsearch_checks").unwrap_or(_false.clone()) == _true,
+			syzygy_path: Self::get_arg_value(&args, "syzygy_path").unwrap_or_default(),
+			tablebase_path: Self::get_arg_value(&args, "tablebase_path").unwrap_or("tablebases".to_string()),
+			experience_file: Self::get_arg_value(&args, "experience_file").unwrap_or_default(),
+			experience_size: Self::get_arg_value(&args, "experience_size").and_then(|size| size.parse::<usize>().ok()).unwrap_or(DEFAULT_EXPERIENCE_SIZE),
+			eval_file: Self::get_arg_value(&args, "eval_file").unwrap_or_default(),
+			nnue: Self::get_arg_value(&args, "nnue").unwrap_or(_false.clone()) == _true,
 		}
 	}
 
@@ -53,11 +84,20 @@ pub struct Bot {
 	think_timer: Instant,
 	pub search_cancelled: bool,
 
-	opening_book: OpeningBook,
-	in_opening_book: bool,
+	pub opening_book: OpeningBook,
+	pub polyglot_book: PolyglotBook,
+	pub book_learning: BookLearning,
 
 	move_sorter: MoveSorter,
 	pub transposition_table: TranspositionTable,
+	pub experience: Experience,
+	// Boards get these from the Bot, see Board::set_eval_params
+	pub eval_params: EvalParams,
+
+	pub syzygy: Syzygy,
+	pub tablebases: Arc<EndgameTablebases>,
+	// The moves at the root that keep the tablebase result, if the root position is in the tablebases
+	tb_root_moves: Option<MoveList>,
 
 	pub best_move: MoveData,
 	best_move_this_iteration: MoveData,
@@ -73,18 +113,25 @@ pub struct Bot {
 
 impl Bot {
 	pub fn new(config: BotConfig) -> Self {
-		Self {
+		let mut bot = Self {
 			config: config.clone(),
 
 			time_to_think: 0.0,
 			think_timer: Instant::now(),
 			search_cancelled: false,
 
-			opening_book: OpeningBook::create(),
-			in_opening_book: config.opening_book,
+			opening_book: OpeningBook::open(&config.book_file),
+			polyglot_book: PolyglotBook::open(&config.book_file),
+			book_learning: BookLearning::open(&config.book_file),
 
 			move_sorter: MoveSorter::new(),
 			transposition_table: TranspositionTable::empty(config.hash_size),
+			experience: Experience::open(&config.experience_file, config.experience_size),
+			eval_params: EvalParams::open(&config.eval_file),
+
+			syzygy: Syzygy::new(&config.syzygy_path),
+			tablebases: Arc::new(EndgameTablebases::new(&config.tablebase_path)),
+			tb_root_moves: None,
 
 			best_move: NULL_MOVE,
 			best_move_this_iteration: NULL_MOVE,
@@ -96,7 +143,11 @@ impl Bot {
 
 			positions_searched: 0,
 			quiescence_searched: 0,
-		}
+		};
+
+		bot.experience.preload(&mut bot.transposition_table);
+
+		bot
 	}
 
 	pub fn debugln(&self, output: String) {
@@ -105,11 +156,15 @@ impl Bot {
 		}
 	}
 
+	pub fn total_nodes(&self) -> u128 {
+		self.positions_searched + self.quiescence_searched
+	}
+
 	pub fn print_uci_info(&self, current_depth: u8, score_type: &'static str, score: i32, pv: String) {
-		let total_nodes = self.positions_searched + self.quiescence_searched;
+		let total_nodes = self.total_nodes();
 		let time_elapsed = self.think_timer.elapsed();
 
-		println!("info depth {depth} seldepth {seldepth} score {score_type} {score} currmove {currmove} pv {pv}currline {pv}nodes {nodes} time {time} nps {nps}",
+		println!("info depth {depth} seldepth {seldepth} score {score_type} {score} currmove {currmove} pv {pv}currline {pv}nodes {nodes} tbhits {tbhits} time {time} nps {nps}",
 			depth = current_depth,
 			seldepth = self.sel_depth,
 			score_type = score_type,
@@ -117,18 +172,37 @@ impl Bot {
 			currmove = self.best_move.to_coordinates(),
 			pv = pv,
 			nodes = total_nodes,
+			tbhits = self.syzygy.hits + self.tablebases.hits.load(Ordering::Relaxed) as u128,
 			time = time_elapsed.as_millis(),
 			nps = total_nodes as f32 / time_elapsed.as_secs_f32(),
 		);
 	}
 
-	pub fn start(&mut self, board: &mut Board, moves: String, my_time: f32, depth: u8) {
-		if self.in_opening_book {
-			let opening_move = self.opening_book.get_opening_move(moves);
-			if opening_move == NULL_MOVE {
-				self.in_opening_book = false;
-			} else {
-				self.best_move = opening_move;
+	pub fn start(&mut self, board: &mut Board, my_time: f32, depth: u8) {
+		board.set_nnue(self.config.nnue);
+		board.tablebases = Some(Arc::clone(&self.tablebases));
+
+		// The books are looked up by position, so this works after transpositions, and can get back into the book later
+		if self.config.opening_book {
+			// A book file replaces the built-in lines
+			let mut book_moves =
+				if self.polyglot_book.is_open() {
+					self.polyglot_book.get_moves(board)
+				} else {
+					self.opening_book.get_moves(board)
+				};
+
+			if self.config.book_learning {
+				self.book_learning.adjust_weights(board, &mut book_moves);
+			}
+
+			let book_move = choose_book_move(&book_moves, self.config.book_best_move);
+			if book_move != NULL_MOVE {
+				if self.config.book_learning {
+					self.book_learning.book_move_played(board, book_move);
+				}
+
+				self.best_move = book_move;
 				return;
 			}
 		}
@@ -154,11 +228,17 @@ impl Bot {
 		self.positions_searched = 0;
 		self.quiescence_searched = 0;
 		self.transposition_table.hits = 0;
+		self.syzygy.hits = 0;
+		self.tablebases.hits.store(0, Ordering::Relaxed);
 
 		self.move_sorter.clear();
 
+		self.tb_root_moves = self.syzygy.root_moves(board);
+
 		// TODO: tweak this
 		let mut window = 40;
+		// The last depth that was searched all the way through
+		let mut depth_completed = 0;
 
 		self.think_timer = Instant::now();
 		for current_depth in 1..=depth {
@@ -185,6 +265,10 @@ impl Bot {
 			if !search_cancelled_prematurely {
 				self.best_move = self.best_move_this_iteration;
 				self.evaluation = self.evaluation_this_iteration;
+
+				if !self.search_cancelled {
+					depth_completed = current_depth;
+				}
 			}
 
 			if evaluation_is_mate(self.evaluation) {
@@ -220,8 +304,9 @@ impl Bot {
 		}
 
 		if self.best_move == NULL_MOVE {
-			let legal_moves = board.get_pseudo_legal_moves_for_color(board.white_to_move, false);
-			for m in legal_moves {
+			let mut legal_moves = MoveList::new();
+			board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut legal_moves);
+			for m in legal_moves.iter().copied() {
 				if board.make_move(m) {
 					board.undo_last_move(); // Technically not necessary, because all moves get undone upon receiving the "position" command, but makes me happy :>
 					self.best_move = m;
@@ -231,6 +316,8 @@ impl Bot {
 			self.debugln("Failed to find a move in time, defaulting to first legal move :(".to_string());
 		}
 
+		self.experience.record(board.zobrist.key.current, self.best_move, depth_completed, self.evaluation);
+
 		self.debugln(format!("{} seconds", self.think_timer.elapsed().as_secs_f32()));
 
 		if self.config.debug_output {
@@ -243,6 +330,14 @@ impl Bot {
 		self.search_cancelled
 	}
 
+	fn is_root_move_allowed(&self, data: MoveData) -> bool {
+		if let Some(tb_root_moves) = &self.tb_root_moves {
+			tb_root_moves.iter().any(|m| *m == data)
+		} else {
+			true
+		}
+	}
+
 	fn find_pv(&mut self, board: &mut Board, depth: u8) -> String {
 		if depth == 0 {
 			return String::new();
@@ -251,7 +346,12 @@ impl Bot {
 		if let Some(data) = self.transposition_table.get(board.zobrist.key.current) {
 			let hash_move = MoveData::from_binary(data.best_move);
 
-			if !board.play_move(hash_move) {
+			if !board.is_pseudo_legal(hash_move) {
+				return String::new();
+			}
+
+			let hash_move = board.complete_move_data(hash_move);
+			if !board.make_move(hash_move) {
 				return String::new();
 			}
 
@@ -300,13 +400,63 @@ impl Bot {
 
 		let (tt_eval, hash_move) = self.transposition_table.lookup(board.zobrist.key.current, ply, depth, alpha, beta);
 
-		// We don't really want to return from the root node, because if a hash collision occurs (although very rare)
-		// It will return an illegal move
-		if ply > 0 {
-			if let Some(tt_eval) = tt_eval {
+		if let Some(tt_eval) = tt_eval {
+			if ply > 0 {
 				return tt_eval;
 			}
 
+			// At the root the hash move becomes the best move, so because of hash collisions (although very rare)
+			// it has to be checked before it can be trusted
+			if let Some(hash_move) = hash_move {
+				if board.is_pseudo_legal(hash_move)
+				&& self.is_root_move_allowed(hash_move) {
+					let hash_move = board.complete_move_data(hash_move);
+
+					if board.make_move(hash_move) {
+						board.undo_last_move();
+
+						self.best_move_this_iteration = hash_move;
+						self.evaluation_this_iteration = tt_eval;
+
+						return tt_eval;
+					}
+				}
+			}
+		}
+
+		// Maxwell's own tablebases store the exact distance to mate, so they can be returned straight away
+		if ply > 0 {
+			if let Some(evaluation) = self.tablebases.probe(board, ply) {
+				return evaluation;
+			}
+		}
+
+		// Syzygy tablebases
+		// The stored results assume the fifty move counter is 0, so they can only be trusted right after a capture or pawn move
+		if ply > 0
+		&& board.board_state.current.fifty_move_counter == 0
+		&& self.syzygy.can_probe(board) {
+			if let Some(wdl) = self.syzygy.probe_wdl(board) {
+				// Cursed wins and blessed losses are draws because of the 50 move rule
+				let (evaluation, eval_bound) =
+					if wdl == WDL_WIN {
+						(TB_WIN_EVAL - ply as i32, EvalBound::LowerBound)
+					} else if wdl == WDL_LOSS {
+						(-TB_WIN_EVAL + ply as i32, EvalBound::UpperBound)
+					} else {
+						(0, EvalBound::Exact)
+					};
+
+				if eval_bound == EvalBound::Exact
+				|| (eval_bound == EvalBound::LowerBound && evaluation >= beta)
+				|| (eval_bound == EvalBound::UpperBound && evaluation <= alpha) {
+					self.transposition_table.store(board.zobrist.key.current, depth, ply, evaluation, NULL_MOVE, eval_bound);
+					return evaluation;
+				}
+			}
+		}
+
+		if ply > 0 {
 			// Internal Iterative Reductions
 			if depth > 1
 			&& hash_move.is_none() {
@@ -324,7 +474,7 @@ impl Bot {
 		&& !in_check
 		&& !evaluation_is_mate(alpha)
 		&& !evaluation_is_mate(beta) {
-			let static_eval = board.hc_evaluate();
+			let static_eval = self.evaluate(board);
 
 			// Reverse Futility Pruning
 			if depth < 8 // TODO: mess around with this
@@ -373,15 +523,16 @@ impl Bot {
 		}
 
 		if depth == 0 {
-			return self.quiescence_search(board, alpha, beta, true);
+			return self.quiescence_search(board, ply, alpha, beta, true);
 		}
 
 		let mut best_move_this_search = NULL_MOVE;
 		// let mut eval_bound = EvalBound::UpperBound;
 
-		let sorted_moves = self.move_sorter.sort_moves(
-			board.white_to_move,
-			board.get_pseudo_legal_moves_for_color(board.white_to_move, false),
+		let last_move = board.get_last_move();
+		let mut move_picker = MovePicker::new(
+			&self.move_sorter,
+			last_move,
 			/*
 			The best move is _not_ the same as the hash move, because we could have
 			found a new best move right before exiting the search, before tt.store gets called
@@ -398,7 +549,12 @@ impl Bot {
 		let mut found_pv = false;
 
 		let mut legal_moves_found = 0;
-		for (_score, m) in sorted_moves {
+		while let Some(m) = move_picker.next(board, &self.move_sorter) {
+			if ply == 0
+			&& !self.is_root_move_allowed(m) {
+				continue;
+			}
+
 			if !board.make_move(m) {
 				continue;
 			}
@@ -462,6 +618,7 @@ impl Bot {
 				if m.capture == NO_PIECE as u8 {
 					self.move_sorter.add_killer_move(m, ply as usize);
 					self.move_sorter.history[board.white_to_move as usize][m.from as usize][m.to as usize] += (depth * depth) as i32;
+					self.move_sorter.add_counter_move(m, last_move);
 				}
 
 				return beta;
@@ -499,7 +656,21 @@ impl Bot {
 		alpha
 	}
 
-	fn quiescence_search(&mut self, board: &mut Board, mut alpha: i32, beta: i32, is_root: bool) -> i32 {
+	// Whichever evaluation is selected, from the side to move's perspective
+	fn evaluate(&self, board: &mut Board) -> i32 {
+		if self.config.nnue {
+			board.tablebase_evaluate().unwrap_or_else(|| board.nnue_evaluate())
+		} else {
+			board.hc_evaluate()
+		}
+	}
+
+	// Just the quiescence search from the side to move's perspective, for positions that might not be quiet (used by the tuner)
+	pub fn quiet_evaluation(&mut self, board: &mut Board) -> i32 {
+		self.quiescence_search(board, 0, -CHECKMATE_EVAL, CHECKMATE_EVAL, false)
+	}
+
+	fn quiescence_search(&mut self, board: &mut Board, ply: u8, mut alpha: i32, beta: i32, is_root: bool) -> i32 {
 		if self.should_cancel_search() {
 			return 0;
 		}
@@ -510,7 +681,33 @@ impl Bot {
 
 		self.quiescence_searched += 1;
 
-		let evaluation = board.hc_evaluate();
+		if let Some(evaluation) = self.tablebases.probe(board, ply) {
+			return evaluation;
+		}
+
+		// Quiescence results are stored at depth 0, so any entry from the main search can be used here too
+		let (tt_eval, hash_move) = self.transposition_table.lookup(board.zobrist.key.current, ply, 0, alpha, beta);
+		if let Some(tt_eval) = tt_eval {
+			return tt_eval;
+		}
+
+		if ply >= MAX_DEPTH {
+			return self.evaluate(board);
+		}
+
+		let original_alpha = alpha;
+		let mut best_move_this_search = NULL_MOVE;
+
+		let in_check = board.king_in_check(board.white_to_move);
+
+		// Standing pat isn't allowed when in check, because every move might lose
+		let evaluation =
+			if in_check {
+				-CHECKMATE_EVAL
+			} else {
+				self.evaluate(board)
+			};
+
 		if evaluation >= beta {
 			return beta;
 		}
@@ -519,15 +716,18 @@ impl Bot {
 			alpha = evaluation;
 		}
 
-		let moves = board.get_pseudo_legal_moves_for_color(board.white_to_move, true);
-		if moves.is_empty() {
-			return evaluation;
-		}
+		// When in check, all evasions are searched
+		let mut move_picker =
+			if in_check {
+				MovePicker::new(&self.move_sorter, board.get_last_move(), hash_move.unwrap_or(NULL_MOVE), ply as usize)
+			} else {
+				MovePicker::captures(hash_move.unwrap_or(NULL_MOVE), is_root && self.config.qsearch_checks)
+			};
 
-		let sorted_moves = self.move_sorter.sort_moves(board.white_to_move, moves, NULL_MOVE, usize::MAX);
-		for (_score, m) in sorted_moves {
+		let mut legal_moves_found = 0;
+		while let Some(m) = move_picker.next(board, &self.move_sorter) {
 			// Delta Pruning
-			if !board.king_in_check(board.white_to_move) {
+			if !in_check {
 				let threshold = QUEEN_WORTH +
 					if PROMOTABLE.contains(&m.flag) {
 						QUEEN_WORTH - PAWN_WORTH
@@ -544,18 +744,42 @@ impl Bot {
 				continue;
 			}
 
-			let evaluation = -self.quiescence_search(board, -beta, -alpha, false);
+			legal_moves_found += 1;
+
+			let evaluation = -self.quiescence_search(board, ply + 1, -beta, -alpha, false);
 			board.undo_last_move();
 
+			if self.should_cancel_search() {
+				return 0;
+			}
+
 			if evaluation >= beta {
+				self.transposition_table.store(board.zobrist.key.current, 0, ply, beta, m, EvalBound::LowerBound);
 				return beta;
 			}
 
 			if evaluation > alpha {
+				best_move_this_search = m;
 				alpha = evaluation;
 			}
 		}
 
+		if in_check
+		&& legal_moves_found == 0 {
+			let mate_score = CHECKMATE_EVAL - ply as i32;
+			return -mate_score;
+		}
+
+		// If standing pat raised alpha then it's still an exact score, even without a best move
+		let eval_bound =
+			if alpha > original_alpha {
+				EvalBound::Exact
+			} else {
+				EvalBound::UpperBound
+			};
+
+		self.transposition_table.store(board.zobrist.key.current, 0, ply, alpha, best_move_this_search, eval_bound);
+
 		alpha
 	}
 }
\ No newline at end of file
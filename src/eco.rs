@@ -0,0 +1,306 @@
+/*
+ECO (Encyclopaedia of Chess Openings) classification
+
+Every line below is played through once (the first time it's needed), and the position it ends in is stored with its code and name,
+so openings are recognized by position, even when the game got there through a different move order
+
+This isn't every subvariation in the ECO books, but every volume (A00 - E99) has its main openings and their most common lines
+*/
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use crate::pgn::san_to_move_data;
+use crate::polyglot::polyglot_key;
+use crate::{Board, STARTING_FEN};
+
+pub struct EcoOpening {
+	pub code: &'static str,
+	pub name: &'static str,
+}
+
+pub const ECO_LINES: &[(&str, &str, &str)] = &[
+	// A: flank openings
+	("A00", "Polish Opening", "b4"),
+	("A00", "Grob Opening", "g4"),
+	("A00", "Van't Kruijs Opening", "e3"),
+	("A00", "Mieses Opening", "d3"),
+	("A00", "Saragossa Opening", "c3"),
+	("A00", "Hungarian Opening", "g3"),
+	("A00", "Anderssen's Opening", "a3"),
+	("A00", "Amar Opening", "Nh3"),
+	("A00", "Durkin Opening", "Na3"),
+	("A00", "Van Geet Opening", "Nc3"),
+	("A01", "Nimzo-Larsen Attack", "b3"),
+	("A02", "Bird's Opening", "f4"),
+	("A02", "Bird's Opening, From's Gambit", "f4 e5"),
+	("A03", "Bird's Opening, Dutch Variation", "f4 d5"),
+	("A04", "Reti Opening", "Nf3"),
+	("A05", "Reti Opening", "Nf3 Nf6"),
+	("A06", "Reti Opening", "Nf3 d5"),
+	("A07", "King's Indian Attack", "Nf3 d5 g3"),
+	("A09", "Reti Opening", "Nf3 d5 c4"),
+	("A10", "English Opening", "c4"),
+	("A11", "English Opening, Caro-Kann Defensive System", "c4 c6"),
+	("A13", "English Opening, Agincourt Defense", "c4 e6"),
+	("A15", "English Opening, Anglo-Indian Defense", "c4 Nf6"),
+	("A16", "English Opening, Anglo-Indian Defense", "c4 Nf6 Nc3"),
+	("A17", "English Opening, Anglo-Indian Defense", "c4 Nf6 Nc3 e6"),
+	("A18", "English Opening, Mikenas-Carls Variation", "c4 Nf6 Nc3 e6 e4"),
+	("A20", "English Opening, King's English Variation", "c4 e5"),
+	("A21", "English Opening, King's English Variation", "c4 e5 Nc3"),
+	("A22", "English Opening, King's English Variation", "c4 e5 Nc3 Nf6"),
+	("A25", "English Opening, Sicilian Reversed", "c4 e5 Nc3 Nc6"),
+	("A27", "English Opening, Three Knights System", "c4 e5 Nc3 Nc6 Nf3"),
+	("A28", "English Opening, Four Knights System", "c4 e5 Nc3 Nc6 Nf3 Nf6"),
+	("A29", "English Opening, Four Knights, Kingside Fianchetto", "c4 e5 Nc3 Nc6 Nf3 Nf6 g3"),
+	("A30", "English Opening, Symmetrical Variation", "c4 c5"),
+	("A34", "English Opening, Symmetrical Variation", "c4 c5 Nc3"),
+	("A40", "Queen's Pawn Game", "d4"),
+	("A40", "Englund Gambit", "d4 e5"),
+	("A40", "Modern Defense", "d4 g6"),
+	("A40", "Horwitz Defense", "d4 e6"),
+	("A41", "Queen's Pawn Game", "d4 d6"),
+	("A43", "Old Benoni Defense", "d4 c5"),
+	("A45", "Indian Game", "d4 Nf6"),
+	("A45", "Trompowsky Attack", "d4 Nf6 Bg5"),
+	("A46", "Indian Game", "d4 Nf6 Nf3"),
+	("A47", "Queen's Indian Defense", "d4 Nf6 Nf3 b6"),
+	("A48", "East Indian Defense", "d4 Nf6 Nf3 g6"),
+	("A48", "London System", "d4 Nf6 Nf3 g6 Bf4"),
+	("A50", "Indian Game", "d4 Nf6 c4"),
+	("A51", "Budapest Gambit", "d4 Nf6 c4 e5"),
+	("A53", "Old Indian Defense", "d4 Nf6 c4 d6"),
+	("A56", "Benoni Defense", "d4 Nf6 c4 c5"),
+	("A57", "Benko Gambit", "d4 Nf6 c4 c5 d5 b5"),
+	("A60", "Modern Benoni", "d4 Nf6 c4 c5 d5 e6"),
+	("A80", "Dutch Defense", "d4 f5"),
+	("A82", "Dutch Defense, Staunton Gambit", "d4 f5 e4"),
+	("A84", "Dutch Defense", "d4 f5 c4"),
+	("A85", "Dutch Defense", "d4 f5 c4 Nf6 Nc3"),
+	("A86", "Dutch Defense", "d4 f5 c4 Nf6 g3"),
+	("A87", "Dutch Defense, Leningrad Variation", "d4 f5 c4 Nf6 g3 g6 Bg2 Bg7 Nf3"),
+	("A90", "Dutch Defense, Classical Variation", "d4 f5 c4 Nf6 g3 e6 Bg2"),
+
+	// B: semi-open games other than the French
+	("B00", "King's Pawn Game", "e4"),
+	("B00", "Nimzowitsch Defense", "e4 Nc6"),
+	("B00", "Owen Defense", "e4 b6"),
+	("B01", "Scandinavian Defense", "e4 d5"),
+	("B01", "Scandinavian Defense", "e4 d5 exd5 Qxd5"),
+	("B01", "Scandinavian Defense, Modern Variation", "e4 d5 exd5 Nf6"),
+	("B02", "Alekhine Defense", "e4 Nf6"),
+	("B03", "Alekhine Defense", "e4 Nf6 e5 Nd5 d4"),
+	("B04", "Alekhine Defense, Modern Variation", "e4 Nf6 e5 Nd5 d4 d6 Nf3"),
+	("B06", "Modern Defense", "e4 g6"),
+	("B07", "Pirc Defense", "e4 d6 d4 Nf6"),
+	("B08", "Pirc Defense, Classical Variation", "e4 d6 d4 Nf6 Nc3 g6 Nf3"),
+	("B09", "Pirc Defense, Austrian Attack", "e4 d6 d4 Nf6 Nc3 g6 f4"),
+	("B10", "Caro-Kann Defense", "e4 c6"),
+	("B11", "Caro-Kann Defense, Two Knights Attack", "e4 c6 Nc3 d5 Nf3"),
+	("B12", "Caro-Kann Defense", "e4 c6 d4 d5"),
+	("B12", "Caro-Kann Defense, Advance Variation", "e4 c6 d4 d5 e5"),
+	("B13", "Caro-Kann Defense, Exchange Variation", "e4 c6 d4 d5 exd5 cxd5"),
+	("B13", "Caro-Kann Defense, Panov Attack", "e4 c6 d4 d5 exd5 cxd5 c4"),
+	("B15", "Caro-Kann Defense", "e4 c6 d4 d5 Nc3"),
+	("B15", "Caro-Kann Defense", "e4 c6 d4 d5 Nc3 dxe4 Nxe4"),
+	("B17", "Caro-Kann Defense, Karpov Variation", "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Nd7"),
+	("B18", "Caro-Kann Defense, Classical Variation", "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Bf5"),
+	("B20", "Sicilian Defense", "e4 c5"),
+	("B21", "Sicilian Defense, Smith-Morra Gambit", "e4 c5 d4 cxd4 c3"),
+	("B22", "Sicilian Defense, Alapin Variation", "e4 c5 c3"),
+	("B23", "Sicilian Defense, Closed", "e4 c5 Nc3"),
+	("B27", "Sicilian Defense", "e4 c5 Nf3"),
+	("B28", "Sicilian Defense, O'Kelly Variation", "e4 c5 Nf3 a6"),
+	("B29", "Sicilian Defense, Nimzowitsch Variation", "e4 c5 Nf3 Nf6"),
+	("B30", "Sicilian Defense, Old Sicilian", "e4 c5 Nf3 Nc6"),
+	("B30", "Sicilian Defense, Rossolimo Variation", "e4 c5 Nf3 Nc6 Bb5"),
+	("B32", "Sicilian Defense, Open", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4"),
+	("B33", "Sicilian Defense, Open", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6"),
+	("B33", "Sicilian Defense, Lasker-Pelikan Variation", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6 Nc3 e5"),
+	("B34", "Sicilian Defense, Accelerated Dragon", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 g6"),
+	("B36", "Sicilian Defense, Accelerated Dragon, Maroczy Bind", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 g6 c4"),
+	("B40", "Sicilian Defense, French Variation", "e4 c5 Nf3 e6"),
+	("B41", "Sicilian Defense, Kan Variation", "e4 c5 Nf3 e6 d4 cxd4 Nxd4 a6"),
+	("B44", "Sicilian Defense, Taimanov Variation", "e4 c5 Nf3 e6 d4 cxd4 Nxd4 Nc6"),
+	("B50", "Sicilian Defense, Modern Variations", "e4 c5 Nf3 d6"),
+	("B51", "Sicilian Defense, Moscow Variation", "e4 c5 Nf3 d6 Bb5+"),
+	("B53", "Sicilian Defense, Chekhover Variation", "e4 c5 Nf3 d6 d4 cxd4 Qxd4"),
+	("B54", "Sicilian Defense, Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4"),
+	("B56", "Sicilian Defense, Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3"),
+	("B57", "Sicilian Defense, Sozin Attack", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 Nc6 Bc4"),
+	("B58", "Sicilian Defense, Classical Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 Nc6"),
+	("B70", "Sicilian Defense, Dragon Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6"),
+	("B72", "Sicilian Defense, Dragon Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6 Be3"),
+	("B76", "Sicilian Defense, Dragon Variation, Yugoslav Attack", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6 Be3 Bg7 f3 O-O"),
+	("B80", "Sicilian Defense, Scheveningen Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 e6"),
+	("B90", "Sicilian Defense, Najdorf Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6"),
+	("B90", "Sicilian Defense, Najdorf Variation, English Attack", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3"),
+	("B92", "Sicilian Defense, Najdorf Variation, Opocensky Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be2"),
+	("B94", "Sicilian Defense, Najdorf Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Bg5"),
+	("B96", "Sicilian Defense, Najdorf Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Bg5 e6"),
+	("B97", "Sicilian Defense, Najdorf Variation, Poisoned Pawn", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Bg5 e6 f4 Qb6"),
+
+	// C: open games and the French
+	("C00", "French Defense", "e4 e6"),
+	("C00", "French Defense", "e4 e6 d4 d5"),
+	("C01", "French Defense, Exchange Variation", "e4 e6 d4 d5 exd5 exd5"),
+	("C02", "French Defense, Advance Variation", "e4 e6 d4 d5 e5"),
+	("C03", "French Defense, Tarrasch Variation", "e4 e6 d4 d5 Nd2"),
+	("C10", "French Defense, Paulsen Variation", "e4 e6 d4 d5 Nc3"),
+	("C10", "French Defense, Rubinstein Variation", "e4 e6 d4 d5 Nc3 dxe4"),
+	("C11", "French Defense, Classical Variation", "e4 e6 d4 d5 Nc3 Nf6"),
+	("C11", "French Defense, Steinitz Variation", "e4 e6 d4 d5 Nc3 Nf6 e5"),
+	("C13", "French Defense, Classical Variation", "e4 e6 d4 d5 Nc3 Nf6 Bg5"),
+	("C15", "French Defense, Winawer Variation", "e4 e6 d4 d5 Nc3 Bb4"),
+	("C18", "French Defense, Winawer Variation", "e4 e6 d4 d5 Nc3 Bb4 e5 c5 a3"),
+	("C20", "King's Pawn Game", "e4 e5"),
+	("C21", "Center Game", "e4 e5 d4 exd4"),
+	("C21", "Danish Gambit", "e4 e5 d4 exd4 c3"),
+	("C22", "Center Game", "e4 e5 d4 exd4 Qxd4"),
+	("C23", "Bishop's Opening", "e4 e5 Bc4"),
+	("C24", "Bishop's Opening, Berlin Defense", "e4 e5 Bc4 Nf6"),
+	("C25", "Vienna Game", "e4 e5 Nc3"),
+	("C26", "Vienna Game", "e4 e5 Nc3 Nf6"),
+	("C30", "King's Gambit", "e4 e5 f4"),
+	("C31", "King's Gambit Declined, Falkbeer Countergambit", "e4 e5 f4 d5"),
+	("C33", "King's Gambit Accepted", "e4 e5 f4 exf4"),
+	("C34", "King's Gambit Accepted, King's Knight Gambit", "e4 e5 f4 exf4 Nf3"),
+	("C40", "King's Knight Opening", "e4 e5 Nf3"),
+	("C40", "Latvian Gambit", "e4 e5 Nf3 f5"),
+	("C40", "Elephant Gambit", "e4 e5 Nf3 d5"),
+	("C41", "Philidor Defense", "e4 e5 Nf3 d6"),
+	("C42", "Petrov's Defense", "e4 e5 Nf3 Nf6"),
+	("C43", "Petrov's Defense, Modern Attack", "e4 e5 Nf3 Nf6 d4"),
+	("C44", "King's Pawn Game", "e4 e5 Nf3 Nc6"),
+	("C44", "Ponziani Opening", "e4 e5 Nf3 Nc6 c3"),
+	("C44", "Scotch Game", "e4 e5 Nf3 Nc6 d4 exd4"),
+	("C44", "Scotch Gambit", "e4 e5 Nf3 Nc6 d4 exd4 Bc4"),
+	("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4 exd4 Nxd4"),
+	("C46", "Three Knights Opening", "e4 e5 Nf3 Nc6 Nc3"),
+	("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+	("C47", "Four Knights Game, Scotch Variation", "e4 e5 Nf3 Nc6 Nc3 Nf6 d4"),
+	("C48", "Four Knights Game, Spanish Variation", "e4 e5 Nf3 Nc6 Nc3 Nf6 Bb5"),
+	("C49", "Four Knights Game, Double Spanish", "e4 e5 Nf3 Nc6 Nc3 Nf6 Bb5 Bb4"),
+	("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+	("C50", "Italian Game, Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+	("C51", "Italian Game, Evans Gambit", "e4 e5 Nf3 Nc6 Bc4 Bc5 b4"),
+	("C53", "Italian Game, Classical Variation", "e4 e5 Nf3 Nc6 Bc4 Bc5 c3"),
+	("C54", "Italian Game, Classical Variation", "e4 e5 Nf3 Nc6 Bc4 Bc5 c3 Nf6 d4"),
+	("C55", "Italian Game, Two Knights Defense", "e4 e5 Nf3 Nc6 Bc4 Nf6"),
+	("C55", "Italian Game, Two Knights Defense, Modern Bishop's Opening", "e4 e5 Nf3 Nc6 Bc4 Nf6 d3"),
+	("C57", "Italian Game, Two Knights Defense, Knight Attack", "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5"),
+	("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+	("C62", "Ruy Lopez, Steinitz Defense", "e4 e5 Nf3 Nc6 Bb5 d6"),
+	("C63", "Ruy Lopez, Schliemann Defense", "e4 e5 Nf3 Nc6 Bb5 f5"),
+	("C64", "Ruy Lopez, Classical Variation", "e4 e5 Nf3 Nc6 Bb5 Bc5"),
+	("C65", "Ruy Lopez, Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+	("C67", "Ruy Lopez, Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6 O-O Nxe4"),
+	("C68", "Ruy Lopez, Exchange Variation", "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6"),
+	("C70", "Ruy Lopez, Morphy Defense", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4"),
+	("C77", "Ruy Lopez, Morphy Defense", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6"),
+	("C78", "Ruy Lopez, Morphy Defense", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O"),
+	("C80", "Ruy Lopez, Open Variation", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Nxe4"),
+	("C84", "Ruy Lopez, Closed Variation", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7"),
+	("C88", "Ruy Lopez, Closed Variation", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3"),
+	("C89", "Ruy Lopez, Marshall Attack", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 O-O c3 d5"),
+	("C90", "Ruy Lopez, Closed Variation", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O"),
+	("C92", "Ruy Lopez, Closed Variation", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3"),
+
+	// D: closed games and the Grünfeld
+	("D00", "Queen's Pawn Game", "d4 d5"),
+	("D00", "London System", "d4 d5 Bf4"),
+	("D00", "Blackmar-Diemer Gambit", "d4 d5 e4"),
+	("D01", "Richter-Veresov Attack", "d4 d5 Nc3 Nf6 Bg5"),
+	("D02", "Queen's Pawn Game", "d4 d5 Nf3"),
+	("D02", "London System", "d4 d5 Nf3 Nf6 Bf4"),
+	("D04", "Queen's Pawn Game, Colle System", "d4 d5 Nf3 Nf6 e3"),
+	("D06", "Queen's Gambit", "d4 d5 c4"),
+	("D07", "Queen's Gambit Declined, Chigorin Defense", "d4 d5 c4 Nc6"),
+	("D08", "Queen's Gambit Declined, Albin Countergambit", "d4 d5 c4 e5"),
+	("D10", "Slav Defense", "d4 d5 c4 c6"),
+	("D11", "Slav Defense", "d4 d5 c4 c6 Nf3"),
+	("D12", "Slav Defense", "d4 d5 c4 c6 Nf3 Nf6 e3 Bf5"),
+	("D15", "Slav Defense", "d4 d5 c4 c6 Nf3 Nf6 Nc3"),
+	("D16", "Slav Defense", "d4 d5 c4 c6 Nf3 Nf6 Nc3 dxc4 a4"),
+	("D17", "Slav Defense, Czech Variation", "d4 d5 c4 c6 Nf3 Nf6 Nc3 dxc4 a4 Bf5"),
+	("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+	("D21", "Queen's Gambit Accepted", "d4 d5 c4 dxc4 Nf3"),
+	("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+	("D31", "Queen's Gambit Declined", "d4 d5 c4 e6 Nc3"),
+	("D32", "Tarrasch Defense", "d4 d5 c4 e6 Nc3 c5"),
+	("D35", "Queen's Gambit Declined", "d4 d5 c4 e6 Nc3 Nf6"),
+	("D35", "Queen's Gambit Declined, Exchange Variation", "d4 d5 c4 e6 Nc3 Nf6 cxd5"),
+	("D37", "Queen's Gambit Declined", "d4 d5 c4 e6 Nc3 Nf6 Nf3"),
+	("D38", "Queen's Gambit Declined, Ragozin Defense", "d4 d5 c4 e6 Nc3 Nf6 Nf3 Bb4"),
+	("D43", "Semi-Slav Defense", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6"),
+	("D45", "Semi-Slav Defense", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6 e3"),
+	("D70", "Neo-Grünfeld Defense", "d4 Nf6 c4 g6 f3 d5"),
+	("D80", "Grünfeld Defense", "d4 Nf6 c4 g6 Nc3 d5"),
+	("D85", "Grünfeld Defense, Exchange Variation", "d4 Nf6 c4 g6 Nc3 d5 cxd5 Nxd5"),
+	("D90", "Grünfeld Defense, Three Knights Variation", "d4 Nf6 c4 g6 Nc3 d5 Nf3"),
+
+	// E: Indian defenses
+	("E00", "Indian Game", "d4 Nf6 c4 e6"),
+	("E01", "Catalan Opening", "d4 Nf6 c4 e6 g3"),
+	("E10", "Indian Game", "d4 Nf6 c4 e6 Nf3"),
+	("E11", "Bogo-Indian Defense", "d4 Nf6 c4 e6 Nf3 Bb4+"),
+	("E12", "Queen's Indian Defense", "d4 Nf6 c4 e6 Nf3 b6"),
+	("E15", "Queen's Indian Defense", "d4 Nf6 c4 e6 Nf3 b6 g3"),
+	("E20", "Nimzo-Indian Defense", "d4 Nf6 c4 e6 Nc3 Bb4"),
+	("E21", "Nimzo-Indian Defense, Three Knights Variation", "d4 Nf6 c4 e6 Nc3 Bb4 Nf3"),
+	("E24", "Nimzo-Indian Defense, Sämisch Variation", "d4 Nf6 c4 e6 Nc3 Bb4 a3 Bxc3+ bxc3"),
+	("E30", "Nimzo-Indian Defense, Leningrad Variation", "d4 Nf6 c4 e6 Nc3 Bb4 Bg5"),
+	("E32", "Nimzo-Indian Defense, Classical Variation", "d4 Nf6 c4 e6 Nc3 Bb4 Qc2"),
+	("E40", "Nimzo-Indian Defense, Rubinstein Variation", "d4 Nf6 c4 e6 Nc3 Bb4 e3"),
+	("E60", "King's Indian Defense", "d4 Nf6 c4 g6"),
+	("E61", "King's Indian Defense", "d4 Nf6 c4 g6 Nc3"),
+	("E62", "King's Indian Defense, Fianchetto Variation", "d4 Nf6 c4 g6 Nc3 Bg7 Nf3 d6 g3"),
+	("E70", "King's Indian Defense", "d4 Nf6 c4 g6 Nc3 Bg7 e4"),
+	("E70", "King's Indian Defense, Normal Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6"),
+	("E73", "King's Indian Defense", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Be2"),
+	("E76", "King's Indian Defense, Four Pawns Attack", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f4"),
+	("E80", "King's Indian Defense, Sämisch Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f3"),
+	("E90", "King's Indian Defense", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3"),
+	("E92", "King's Indian Defense, Classical Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5"),
+	("E94", "King's Indian Defense, Orthodox Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 O-O"),
+	("E97", "King's Indian Defense, Mar del Plata Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 O-O Nc6 d5 Ne7"),
+];
+
+pub struct EcoTable {
+	pub positions: HashMap<u64, EcoOpening>,
+}
+
+impl EcoTable {
+	pub fn new() -> Self {
+		let mut table = Self {
+			positions: HashMap::new(),
+		};
+
+		let mut board = Board::from_fen(STARTING_FEN);
+		for (code, name, line) in ECO_LINES.iter() {
+			for san in line.split(' ') {
+				let Some(data) = san_to_move_data(&mut board, san) else {
+					panic!("Illegal ECO move: {} in {} {}", san, code, name);
+				};
+				board.make_move(data);
+			}
+
+			// When two lines transpose into the same position, the first one wins
+			table.positions.entry(polyglot_key(&board)).or_insert(EcoOpening { code, name });
+
+			while board.undo_last_move() {}
+		}
+
+		table
+	}
+
+	pub fn get(&self, board: &Board) -> Option<&EcoOpening> {
+		self.positions.get(&polyglot_key(board))
+	}
+}
+
+// Only built the first time an opening is looked up
+pub fn eco_table() -> &'static EcoTable {
+	static TABLE: OnceLock<EcoTable> = OnceLock::new();
+	TABLE.get_or_init(EcoTable::new)
+}
@@ -0,0 +1,944 @@
+/*
+Maxwell's own endgame tablebases for 3 and 4 piece endings, made with retrograde analysis
+
+Every position gets its distance to mate in plies, working backwards from the checkmates:
+a position is won in n + 1 if any move leads to a position that's lost in n,
+and it's lost in n + 1 if every move leads to a won position, and the slowest of those wins is n.
+Captures and promotions leave the table, so those are looked up in the smaller tables, which get generated first.
+
+The search probes them for exact mate scores, and the evaluation probes them too (as known wins, so they don't look like real mates)
+
+The white king is always mirrored into the a1-d1-d4 triangle (or onto the a-d files when there are pawns),
+so each table only has to store 10 (or 32) king squares, times 64 squares for every other piece, times 2 sides to move
+
+File format (.mtb):
+	"MXTB", version, piece count, the pieces, entry count (u32), block count (u32), block offsets (u32 each),
+	then every block of BLOCK_SIZE entries, run length encoded as (value, run length) pairs
+*/
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use crate::board::MoveGenType;
+use crate::move_list::MoveList;
+use crate::move_data::DOUBLE_PAWN_PUSH_FLAG;
+use crate::utils::{CHECKMATE_EVAL, pop_lsb};
+use crate::endgames::KNOWN_WIN_EVAL;
+use crate::pieces::*;
+use crate::{Board, STARTING_FEN};
+
+pub const TB_FILE_EXTENSION: &str = "mtb";
+pub const MAX_TB_PIECES: usize = 4;
+
+const MAGIC: &[u8; 4] = b"MXTB";
+const VERSION: u8 = 1;
+const BLOCK_SIZE: usize = 1024;
+
+// 0 = draw, 1..=127 = win in (value * 2 - 1) plies, 128..=253 = loss in ((value - 128) * 2) plies
+const DRAW: u8 = 0;
+const LOSS: u8 = 128;
+// These two only exist while generating
+const INVALID: u8 = 254;
+const UNKNOWN: u8 = 255;
+
+fn win_in(plies: usize) -> u8 { plies.div_ceil(2) as u8 }
+fn loss_in(plies: usize) -> u8 { LOSS + (plies / 2) as u8 }
+fn is_win(value: u8) -> bool { value != DRAW && value < LOSS }
+fn is_loss(value: u8) -> bool { (LOSS..INVALID).contains(&value) }
+
+fn plies_to_mate(value: u8) -> usize {
+	if is_win(value) {
+		value as usize * 2 - 1
+	} else {
+		(value - LOSS) as usize * 2
+	}
+}
+
+// The order the tables get generated in when generating all of them
+const TB_PIECE_TYPES: [usize; 5] = [QUEEN, ROOK, BISHOP, KNIGHT, PAWN];
+
+// 0 = nothing, 1 = mirror files, 2 = mirror ranks, 4 = flip along the a1-h8 diagonal
+fn transform_square(transform: usize, square: u8) -> u8 {
+	let mut file = square % 8;
+	let mut rank = 7 - square / 8;
+
+	if transform & 4 != 0 {
+		std::mem::swap(&mut file, &mut rank);
+	}
+
+	if transform & 1 != 0 {
+		file = 7 - file;
+	}
+
+	if transform & 2 != 0 {
+		rank = 7 - rank;
+	}
+
+	(7 - rank) * 8 + file
+}
+
+pub struct EndgameTable {
+	pub name: String,
+	path: PathBuf,
+	pieces: Vec<usize>, // White king first, and pieces of the same type are always next to each other
+	has_pawns: bool,
+
+	// Read from the file the first time the table is probed, None if that failed
+	contents: OnceLock<Option<TableContents>>,
+}
+
+pub struct TableContents {
+	block_offsets: Vec<u32>,
+	data: Vec<u8>,
+}
+
+impl TableContents {
+	fn get(&self, index: usize) -> u8 {
+		let mut remaining = index % BLOCK_SIZE;
+		let mut i = self.block_offsets[index / BLOCK_SIZE] as usize;
+
+		loop {
+			let (value, run) = (self.data[i], self.data[i + 1] as usize);
+			if remaining < run {
+				return value;
+			}
+
+			remaining -= run;
+			i += 2;
+		}
+	}
+}
+
+impl EndgameTable {
+	fn new(counts: &[[u8; 6]; 2], path: PathBuf) -> Self {
+		let mut pieces = vec![];
+		for (side, is_white) in [(0, true), (1, false)] {
+			for piece_type in [KING, QUEEN, ROOK, BISHOP, KNIGHT, PAWN] {
+				for _ in 0..counts[side][piece_type] {
+					pieces.push(build_piece(is_white, piece_type));
+				}
+			}
+		}
+
+		Self {
+			name: material_name(counts),
+			path,
+			has_pawns: counts[0][PAWN] + counts[1][PAWN] > 0,
+			pieces,
+
+			contents: OnceLock::new(),
+		}
+	}
+
+	fn king_squares(&self) -> usize {
+		if self.has_pawns { 32 } else { 10 }
+	}
+
+	fn entry_count(&self) -> usize {
+		self.king_squares() * 64usize.pow(self.pieces.len() as u32 - 1) * 2
+	}
+
+	fn king_region_index(&self, square: u8) -> Option<usize> {
+		let file = (square % 8) as usize;
+		let rank = (7 - square / 8) as usize;
+
+		if file > 3 {
+			None
+		} else if self.has_pawns {
+			Some(rank * 4 + file)
+		} else if rank <= file {
+			Some(file * (file + 1) / 2 + rank)
+		} else {
+			None
+		}
+	}
+
+	fn index(&self, squares: &[u8], white_to_move: bool) -> Option<usize> {
+		let mut index = self.king_region_index(squares[0])?;
+		for square in squares[1..].iter() {
+			index = index * 64 + *square as usize;
+		}
+		Some(index * 2 + white_to_move as usize)
+	}
+
+	fn decode(&self, index: usize) -> ([u8; MAX_TB_PIECES], bool) {
+		let mut squares = [0; MAX_TB_PIECES];
+		let white_to_move = index % 2 == 1;
+		let mut index = index / 2;
+
+		for i in (1..self.pieces.len()).rev() {
+			squares[i] = (index % 64) as u8;
+			index /= 64;
+		}
+
+		// Turn the king region index back into a square
+		squares[0] =
+			if self.has_pawns {
+				((7 - index / 4) * 8 + index % 4) as u8
+			} else {
+				let mut file = 0;
+				while (file + 1) * (file + 2) / 2 <= index {
+					file += 1;
+				}
+				let rank = index - file * (file + 1) / 2;
+				((7 - rank) * 8 + file) as u8
+			};
+
+		(squares, white_to_move)
+	}
+
+	// Every index that stores this position: one for each symmetry that puts the white king in the king region
+	fn equivalent_indices(&self, squares: &[u8], white_to_move: bool, indices: &mut Vec<usize>) {
+		indices.clear();
+
+		let transforms = if self.has_pawns { 2 } else { 8 };
+		for transform in 0..transforms {
+			let mut transformed = [0; MAX_TB_PIECES];
+			for i in 0..self.pieces.len() {
+				transformed[i] = transform_square(transform, squares[i]);
+			}
+
+			self.sort_identical_pieces(&mut transformed);
+
+			if let Some(index) = self.index(&transformed[..self.pieces.len()], white_to_move) {
+				if !indices.contains(&index) {
+					indices.push(index);
+				}
+			}
+		}
+	}
+
+	fn canonical_index(&self, squares: &[u8], white_to_move: bool) -> Option<usize> {
+		let transforms = if self.has_pawns { 2 } else { 8 };
+		for transform in 0..transforms {
+			if self.king_region_index(transform_square(transform, squares[0])).is_none() {
+				continue;
+			}
+
+			let mut transformed = [0; MAX_TB_PIECES];
+			for i in 0..self.pieces.len() {
+				transformed[i] = transform_square(transform, squares[i]);
+			}
+
+			self.sort_identical_pieces(&mut transformed);
+			return self.index(&transformed[..self.pieces.len()], white_to_move);
+		}
+
+		None
+	}
+
+	// Identical pieces can swap places without changing the position, so they're stored in square order
+	fn sort_identical_pieces(&self, squares: &mut [u8; MAX_TB_PIECES]) {
+		for i in 1..self.pieces.len() {
+			let mut j = i;
+			while j > 0
+			&& self.pieces[j] == self.pieces[j - 1]
+			&& squares[j] < squares[j - 1] {
+				squares.swap(j, j - 1);
+				j -= 1;
+			}
+		}
+	}
+
+	fn contents(&self) -> Option<&TableContents> {
+		self.contents.get_or_init(|| {
+			let contents = self.load();
+			if contents.is_none() {
+				println!("info string Failed to load tablebase {}", self.path.display());
+			}
+			contents
+		}).as_ref()
+	}
+
+	fn load(&self) -> Option<TableContents> {
+		let mut bytes = vec![];
+		File::open(&self.path).ok()?.read_to_end(&mut bytes).ok()?;
+
+		let read_u32 = |offset: usize| -> Option<u32> {
+			Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+		};
+
+		if bytes.get(0..4)? != MAGIC
+		|| *bytes.get(4)? != VERSION {
+			return None;
+		}
+
+		let piece_count = *bytes.get(5)? as usize;
+		if bytes.get(6..6 + piece_count)?.iter().map(|p| *p as usize).ne(self.pieces.iter().copied()) {
+			return None;
+		}
+
+		let mut offset = 6 + piece_count;
+		if read_u32(offset)? as usize != self.entry_count() {
+			return None;
+		}
+
+		let block_count = read_u32(offset + 4)? as usize;
+		offset += 8;
+
+		let block_offsets = (0..block_count).map(|i| read_u32(offset + i * 4)).collect::<Option<Vec<u32>>>()?;
+		offset += block_count * 4;
+
+		Some(TableContents {
+			block_offsets,
+			data: bytes[offset..].to_vec(),
+		})
+	}
+
+	fn save(&self, values: &[u8]) -> std::io::Result<()> {
+		let mut block_offsets = vec![];
+		let mut data = vec![];
+
+		for block in values.chunks(BLOCK_SIZE) {
+			block_offsets.push(data.len() as u32);
+
+			let mut i = 0;
+			while i < block.len() {
+				let value = block[i];
+				let mut run = 1;
+				while i + run < block.len()
+				&& block[i + run] == value
+				&& run < 255 {
+					run += 1;
+				}
+
+				data.push(value);
+				data.push(run as u8);
+				i += run;
+			}
+		}
+
+		let mut bytes = vec![];
+		bytes.extend_from_slice(MAGIC);
+		bytes.push(VERSION);
+		bytes.push(self.pieces.len() as u8);
+		bytes.extend(self.pieces.iter().map(|p| *p as u8));
+		bytes.extend_from_slice(&(values.len() as u32).to_le_bytes());
+		bytes.extend_from_slice(&(block_offsets.len() as u32).to_le_bytes());
+		for offset in block_offsets.iter() {
+			bytes.extend_from_slice(&offset.to_le_bytes());
+		}
+		bytes.extend_from_slice(&data);
+
+		File::create(&self.path)?.write_all(&bytes)
+	}
+}
+
+pub struct EndgameTablebases {
+	pub folder: PathBuf,
+	tables: Vec<EndgameTable>,
+	keys: HashMap<u64, usize>,
+
+	// Counted by the search and the evaluation, which share the tables
+	pub hits: AtomicU64,
+}
+
+impl EndgameTablebases {
+	pub fn new(folder: &str) -> Self {
+		let mut tablebases = Self {
+			folder: PathBuf::from(folder),
+			tables: vec![],
+			keys: HashMap::new(),
+
+			hits: AtomicU64::new(0),
+		};
+
+		if folder.is_empty() {
+			return tablebases;
+		}
+
+		if let Ok(entries) = std::fs::read_dir(folder) {
+			for entry in entries.flatten() {
+				let path = entry.path();
+
+				if path.extension().and_then(|s| s.to_str()) != Some(TB_FILE_EXTENSION) {
+					continue;
+				}
+
+				if let Some(counts) = path.file_stem().and_then(|s| s.to_str()).and_then(parse_material) {
+					tablebases.add_table(EndgameTable::new(&counts, path));
+				}
+			}
+		}
+
+		tablebases
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.tables.is_empty()
+	}
+
+	fn add_table(&mut self, table: EndgameTable) -> usize {
+		let counts = parse_material(&table.name).unwrap();
+		let key = material_key(&counts);
+
+		// A table that just got generated replaces the old one, so the new file gets loaded
+		if let Some(index) = self.keys.get(&key) {
+			self.tables[*index] = table;
+			return *index;
+		}
+
+		self.keys.insert(key, self.tables.len());
+		self.tables.push(table);
+		self.tables.len() - 1
+	}
+
+	// Finds the table for this position, and whether the colors have to be swapped to look it up
+	fn find_table(&self, board: &Board) -> Option<(&EndgameTable, bool)> {
+		let counts = board.material_counts();
+
+		let (index, flipped) =
+			if let Some(index) = self.keys.get(&material_key(&counts)) {
+				(*index, false)
+			} else {
+				(*self.keys.get(&material_key(&[counts[1], counts[0]]))?, true)
+			};
+
+		Some((&self.tables[index], flipped))
+	}
+
+	fn board_squares(table: &EndgameTable, board: &Board, flipped: bool) -> [u8; MAX_TB_PIECES] {
+		let mut squares = [0; MAX_TB_PIECES];
+		let mut piece_bitboards = board.piece_bitboards;
+
+		for (i, piece) in table.pieces.iter().enumerate() {
+			if flipped {
+				let piece = build_piece(!is_piece_white(*piece), get_piece_type(*piece));
+				squares[i] = pop_lsb(&mut piece_bitboards[piece]) ^ 56;
+			} else {
+				squares[i] = pop_lsb(&mut piece_bitboards[*piece]);
+			}
+		}
+
+		squares
+	}
+
+	// Returns the stored value for the side to move, or None if there isn't a table for this position
+	fn probe_value(&self, board: &Board) -> Option<u8> {
+		let piece_count = board.occupied_bitboard().count_ones() as usize;
+		if piece_count > MAX_TB_PIECES
+		|| board.board_state.current.castling_rights != 0 {
+			return None;
+		}
+
+		if piece_count == 2 {
+			return Some(DRAW);
+		}
+
+		let (table, flipped) = self.find_table(board)?;
+		let contents = table.contents()?;
+
+		let squares = Self::board_squares(table, board, flipped);
+		let white_to_move = board.white_to_move != flipped;
+
+		Some(contents.get(table.canonical_index(&squares[..table.pieces.len()], white_to_move)?))
+	}
+
+	// The stored value for the side to move, if the search and the evaluation can use it
+	fn probe_usable_value(&self, board: &Board) -> Option<u8> {
+		// En passant isn't stored in the tables
+		if self.tables.is_empty()
+		|| board.occupied_bitboard().count_ones() as usize > MAX_TB_PIECES
+		|| board.get_last_move().flag == DOUBLE_PAWN_PUSH_FLAG {
+			return None;
+		}
+
+		let value = self.probe_value(board)?;
+
+		// A mate that the fifty move rule gets to first is a draw, so the normal search has to handle it
+		if value != DRAW
+		&& plies_to_mate(value) + board.board_state.current.fifty_move_counter as usize >= 100 {
+			return None;
+		}
+
+		self.hits.fetch_add(1, Ordering::Relaxed);
+		Some(value)
+	}
+
+	// Mate scores like the search uses, so they can be returned straight from the search
+	pub fn probe(&self, board: &Board, ply: u8) -> Option<i32> {
+		let value = self.probe_usable_value(board)?;
+		if value == DRAW {
+			return Some(0);
+		}
+
+		let mate_score = CHECKMATE_EVAL - ply as i32 - plies_to_mate(value) as i32;
+		Some(if is_win(value) { mate_score } else { -mate_score })
+	}
+
+	// For the evaluation, from the side to move's perspective: a win scores below every mate score, and higher the faster it mates
+	pub fn evaluate(&self, board: &Board) -> Option<i32> {
+		let value = self.probe_usable_value(board)?;
+		if value == DRAW {
+			return Some(0);
+		}
+
+		let evaluation = KNOWN_WIN_EVAL - plies_to_mate(value) as i32;
+		Some(if is_win(value) { evaluation } else { -evaluation })
+	}
+
+	// Generates every 3 and 4 piece table that isn't already in the folder
+	pub fn generate_all(&mut self) {
+		let mut names = vec![];
+
+		for (i, first) in TB_PIECE_TYPES.iter().enumerate() {
+			names.push(format!("K{}vK", piece_to_char(build_piece(true, *first))));
+
+			for second in TB_PIECE_TYPES[i..].iter() {
+				names.push(format!("K{}{}vK", piece_to_char(build_piece(true, *first)), piece_to_char(build_piece(true, *second))));
+				names.push(format!("K{}vK{}", piece_to_char(build_piece(true, *first)), piece_to_char(build_piece(true, *second))));
+			}
+		}
+
+		for name in names {
+			self.generate(&name);
+		}
+	}
+
+	pub fn generate(&mut self, name: &str) -> bool {
+		let Some(counts) = parse_material(name) else {
+			println!("Invalid table name: {}", name);
+			return false;
+		};
+
+		let piece_count = counts.iter().flatten().map(|c| *c as usize).sum::<usize>();
+		if !(3..=MAX_TB_PIECES).contains(&piece_count) {
+			println!("Tables can only have 3 or {} pieces", MAX_TB_PIECES);
+			return false;
+		}
+
+		if let Err(error) = std::fs::create_dir_all(&self.folder) {
+			println!("Couldn't create {}: {}", self.folder.display(), error);
+			return false;
+		}
+
+		let mut board = Board::from_fen(STARTING_FEN);
+		self.generate_with_dependencies(&mut board, &counts);
+		true
+	}
+
+	fn has_table(&self, counts: &[[u8; 6]; 2]) -> bool {
+		   self.keys.contains_key(&material_key(counts))
+		|| self.keys.contains_key(&material_key(&[counts[1], counts[0]]))
+	}
+
+	fn generate_with_dependencies(&mut self, board: &mut Board, counts: &[[u8; 6]; 2]) {
+		if self.has_table(counts) {
+			return;
+		}
+
+		// Every material that a capture or promotion can lead to
+		for side in 0..2 {
+			let other = 1 - side;
+
+			for piece_type in PAWN..KING {
+				if counts[side][piece_type] > 0 {
+					let mut captured = *counts;
+					captured[side][piece_type] -= 1;
+					self.generate_dependency(board, &captured);
+				}
+			}
+
+			if counts[side][PAWN] > 0 {
+				for promotion in KNIGHT..=QUEEN {
+					let mut promoted = *counts;
+					promoted[side][PAWN] -= 1;
+					promoted[side][promotion] += 1;
+					self.generate_dependency(board, &promoted);
+
+					for piece_type in KNIGHT..KING {
+						if promoted[other][piece_type] > 0 {
+							let mut captured = promoted;
+							captured[other][piece_type] -= 1;
+							self.generate_dependency(board, &captured);
+						}
+					}
+				}
+			}
+		}
+
+		self.generate_table(board, counts);
+	}
+
+	fn generate_dependency(&mut self, board: &mut Board, counts: &[[u8; 6]; 2]) {
+		// KvK is always a draw
+		if counts.iter().flatten().map(|c| *c as usize).sum::<usize>() <= 2 {
+			return;
+		}
+
+		// The side with more pieces goes first in the name
+		let first_count = counts[0].iter().sum::<u8>();
+		let second_count = counts[1].iter().sum::<u8>();
+		if second_count > first_count {
+			self.generate_with_dependencies(board, &[counts[1], counts[0]]);
+		} else {
+			self.generate_with_dependencies(board, counts);
+		}
+	}
+
+	fn generate_table(&mut self, board: &mut Board, counts: &[[u8; 6]; 2]) {
+		let timer = Instant::now();
+
+		let name = material_name(counts);
+		let path = self.folder.join(format!("{}.{}", name, TB_FILE_EXTENSION));
+		let table = EndgameTable::new(counts, path);
+
+		println!("Generating {}...", name);
+
+		let mut values = vec![UNKNOWN; table.entry_count()];
+
+		// resolved[n] = positions that were just found to be won or lost in n plies
+		// exits[n] = positions with a capture or promotion into a position won or lost in n plies
+		let mut resolved: Vec<Vec<u32>> = vec![vec![]; 256];
+		let mut exits: Vec<Vec<u32>> = vec![vec![]; 256];
+
+		let mut moves = MoveList::new();
+		let mut pieces = vec![];
+
+		for (index, value) in values.iter_mut().enumerate() {
+			let (squares, white_to_move) = table.decode(index);
+
+			if !Self::set_up_position(board, &table, &squares, white_to_move, &mut pieces) {
+				*value = INVALID;
+				continue;
+			}
+
+			moves.clear();
+			board.get_pseudo_legal_moves_for_color(white_to_move, MoveGenType::All, &mut moves);
+
+			let mut legal_moves = 0;
+			for m in moves.iter().copied() {
+				if !board.make_move(m) {
+					continue;
+				}
+
+				legal_moves += 1;
+
+				if m.capture != NO_PIECE as u8
+				|| PROMOTABLE.contains(&m.flag) {
+					let exit_value = self.probe_value(board).expect("Missing a smaller table");
+					if exit_value != DRAW {
+						exits[plies_to_mate(exit_value)].push(index as u32);
+					}
+				}
+
+				board.undo_last_move();
+			}
+
+			if legal_moves == 0 {
+				if board.king_in_check(white_to_move) {
+					*value = LOSS;
+					resolved[0].push(index as u32);
+				} else {
+					*value = DRAW;
+				}
+			}
+		}
+
+		let mut equivalent = vec![];
+		let mut predecessors = vec![];
+
+		for plies in 0..LOSS as usize * 2 - 1 {
+			if resolved[plies].is_empty()
+			&& exits[plies..].iter().all(|e| e.is_empty()) {
+				break;
+			}
+
+			let mut candidates = std::mem::take(&mut exits[plies]);
+			for index in std::mem::take(&mut resolved[plies]) {
+				Self::unmoves(board, &table, index as usize, &mut predecessors);
+				candidates.extend(predecessors.iter().map(|p| *p as u32));
+			}
+
+			for index in candidates {
+				let index = index as usize;
+				if values[index] != UNKNOWN {
+					continue;
+				}
+
+				// Lost positions can only lead to wins, but won positions need every move checked
+				let value =
+					if plies % 2 == 0 {
+						win_in(plies + 1)
+					} else if self.all_moves_lose(board, &table, &values, index, plies) {
+						loss_in(plies + 1)
+					} else {
+						continue;
+					};
+
+				let (squares, white_to_move) = table.decode(index);
+				table.equivalent_indices(&squares[..table.pieces.len()], white_to_move, &mut equivalent);
+				for i in equivalent.iter() {
+					values[*i] = value;
+				}
+
+				resolved[plies + 1].push(index as u32);
+			}
+		}
+
+		for value in values.iter_mut() {
+			if *value == UNKNOWN
+			|| *value == INVALID {
+				*value = DRAW;
+			}
+		}
+
+		if let Err(error) = table.save(&values) {
+			println!("Failed to save {}: {}", table.path.display(), error);
+		}
+
+		let longest_mate = values.iter().filter(|v| is_win(**v)).map(|v| plies_to_mate(*v)).max().unwrap_or(0);
+		println!("Generated {} in {} seconds, longest mate: {} plies", name, timer.elapsed().as_secs_f32(), longest_mate);
+
+		self.add_table(table);
+	}
+
+	// Returns false if the position is impossible: pieces on the same square, pawns on the back ranks,
+	// identical pieces out of order, or the side that just moved is in check
+	fn set_up_position(board: &mut Board, table: &EndgameTable, squares: &[u8], white_to_move: bool, pieces: &mut Vec<(usize, u8)>) -> bool {
+		let mut occupied = 0u64;
+		pieces.clear();
+
+		for (i, piece) in table.pieces.iter().enumerate() {
+			let square = squares[i];
+
+			if occupied & (1 << square) != 0
+			|| (get_piece_type(*piece) == PAWN && !(8..56).contains(&square))
+			|| (i > 0 && table.pieces[i - 1] == *piece && squares[i - 1] > square) {
+				return false;
+			}
+
+			occupied |= 1 << square;
+			pieces.push((*piece, square));
+		}
+
+		board.set_pieces(pieces, white_to_move);
+		!board.king_in_check(!white_to_move)
+	}
+
+	// Every position (that's in this table) that could have moved into this one
+	fn unmoves(board: &mut Board, table: &EndgameTable, index: usize, predecessors: &mut Vec<usize>) {
+		predecessors.clear();
+
+		let (squares, white_to_move) = table.decode(index);
+		let piece_count = table.pieces.len();
+
+		let mut occupied = 0u64;
+		for square in squares[..piece_count].iter() {
+			occupied |= 1 << square;
+		}
+
+		for (i, piece) in table.pieces.iter().enumerate() {
+			// The side that isn't to move made the last move
+			if is_piece_white(*piece) == white_to_move {
+				continue;
+			}
+
+			let square = squares[i] as usize;
+			let is_white = is_piece_white(*piece);
+
+			let mut from_squares = match get_piece_type(*piece) {
+				PAWN => {
+					// White pawns move towards index 0
+					let (back, double_push_rank) = if is_white { (8i32, 4) } else { (-8, 3) };
+					let behind = square as i32 + back;
+
+					let mut from = 0u64;
+					if (8..56).contains(&behind)
+					&& occupied & (1 << behind) == 0 {
+						from |= 1 << behind;
+
+						let double_behind = behind + back;
+						if square / 8 == double_push_rank
+						&& occupied & (1 << double_behind) == 0 {
+							from |= 1 << double_behind;
+						}
+					}
+					from
+				}
+				KNIGHT => board.precalculated_move_data.knight_attacks[square],
+				BISHOP => board.calculate_bishop_attack_bitboard_with_occupancy(square, occupied),
+				ROOK   => board.calculate_rook_attack_bitboard_with_occupancy(square, occupied),
+				QUEEN  =>
+					  board.calculate_bishop_attack_bitboard_with_occupancy(square, occupied)
+					| board.calculate_rook_attack_bitboard_with_occupancy(square, occupied),
+				_ => board.precalculated_move_data.king_attacks[square],
+			} & !occupied;
+
+			while from_squares != 0 {
+				let from = pop_lsb(&mut from_squares);
+
+				let mut previous = squares;
+				previous[i] = from;
+
+				if let Some(index) = table.canonical_index(&previous[..piece_count], !white_to_move) {
+					predecessors.push(index);
+				}
+			}
+		}
+	}
+
+	// True if every legal move leads to a position where the opponent wins within the given number of plies
+	fn all_moves_lose(&mut self, board: &mut Board, table: &EndgameTable, values: &[u8], index: usize, plies: usize) -> bool {
+		let (squares, white_to_move) = table.decode(index);
+
+		let mut pieces = vec![];
+		if !Self::set_up_position(board, table, &squares, white_to_move, &mut pieces) {
+			return false;
+		}
+
+		let mut moves = MoveList::new();
+		board.get_pseudo_legal_moves_for_color(white_to_move, MoveGenType::All, &mut moves);
+
+		for m in moves.iter().copied() {
+			if !board.make_move(m) {
+				continue;
+			}
+
+			let value =
+				if m.capture != NO_PIECE as u8
+				|| PROMOTABLE.contains(&m.flag) {
+					self.probe_value(board)
+				} else {
+					let child_squares = Self::board_squares(table, board, false);
+					table.canonical_index(&child_squares[..table.pieces.len()], board.white_to_move).map(|i| values[i])
+				};
+
+			board.undo_last_move();
+
+			match value {
+				Some(value) if is_win(value) && plies_to_mate(value) <= plies => {}
+				_ => return false,
+			}
+		}
+
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::{Rng, SeedableRng};
+	use rand_pcg::Pcg64;
+	use std::sync::Arc;
+
+	fn temp_folder(name: &str) -> PathBuf {
+		let folder = std::env::temp_dir().join(format!("maxwell_{}_{}", name, std::process::id()));
+		let _ = std::fs::remove_dir_all(&folder);
+		folder
+	}
+
+	fn load_table(folder: &Path, name: &str) -> EndgameTable {
+		let table = EndgameTable::new(&parse_material(name).unwrap(), folder.join(format!("{}.{}", name, TB_FILE_EXTENSION)));
+		assert!(table.contents().is_some());
+		table
+	}
+
+	fn longest_mate(table: &EndgameTable) -> usize {
+		(0..table.entry_count())
+			.map(|i| table.contents().unwrap().get(i))
+			.filter(|value| is_win(*value))
+			.map(plies_to_mate)
+			.max()
+			.unwrap_or(0)
+	}
+
+	#[test]
+	fn generated_tables_have_known_results() {
+		let folder = temp_folder("tb_test");
+		let mut tablebases = EndgameTablebases::new(folder.to_str().unwrap());
+
+		// KPvK needs KQvK and KRvK for the promotions, so those get generated too
+		assert!(tablebases.generate("KPvK"));
+		assert!(tablebases.generate("KRvK"));
+
+		let tablebases = Arc::new(EndgameTablebases::new(folder.to_str().unwrap()));
+		let probe = |fen: &str| tablebases.probe(&Board::from_fen(fen), 0);
+
+		// Qa8# and Rh8#
+		assert_eq!(probe("8/8/8/8/8/8/8/k1K4Q w - - 0 1"), Some(CHECKMATE_EVAL - 1));
+		assert_eq!(probe("k7/8/1K6/8/8/8/8/7R w - - 0 1"), Some(CHECKMATE_EVAL - 1));
+
+		// Whoever has the opposition decides it
+		assert!(probe("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1").unwrap() < -CHECKMATE_EVAL + 100);
+		assert_eq!(probe("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"), Some(0));
+
+		// The king on the 6th in front of its pawn always wins, but behind it it's stalemate
+		assert!(probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1").unwrap() > CHECKMATE_EVAL - 100);
+		assert_eq!(probe("4k3/8/4P3/4K3/8/8/8/8 w - - 0 1"), Some(0));
+
+		// The king in front of a rook pawn
+		assert_eq!(probe("8/8/8/8/8/k7/P7/K7 w - - 0 1"), Some(0));
+
+		// The longest mates are 10 moves for KQvK, 16 for KRvK and 28 for KPvK
+		assert_eq!(longest_mate(&load_table(&folder, "KQvK")), 19);
+		assert_eq!(longest_mate(&load_table(&folder, "KRvK")), 31);
+		assert_eq!(longest_mate(&load_table(&folder, "KPvK")), 55);
+
+		// The mate has to come before the fifty move rule
+		let mut board = Board::from_fen("k7/8/1K6/8/8/8/8/7R w - - 98 80");
+		assert_eq!(tablebases.probe(&board, 0), Some(CHECKMATE_EVAL - 1));
+		board.board_state.current.fifty_move_counter = 99;
+		assert_eq!(tablebases.probe(&board, 0), None);
+
+		let board = Board::from_fen("8/8/8/8/8/k7/P7/K7 w - - 99 80");
+		assert_eq!(tablebases.probe(&board, 0), Some(0));
+
+		// The evaluation gets the same results, scaled down to known wins
+		let mut board = Board::from_fen("8/8/8/8/8/8/8/k1K4Q w - - 0 1");
+		board.tablebases = Some(Arc::clone(&tablebases));
+		assert_eq!(board.hc_evaluate(), KNOWN_WIN_EVAL - 1);
+
+		let mut board = Board::from_fen("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1");
+		board.tablebases = Some(Arc::clone(&tablebases));
+		assert!(board.hc_evaluate() < -KNOWN_WIN_EVAL + 100);
+		assert_eq!(board.tablebase_evaluate(), Some(board.hc_evaluate()));
+
+		let mut board = Board::from_fen("8/8/8/8/8/k7/P7/K7 w - - 0 1");
+		board.tablebases = Some(Arc::clone(&tablebases));
+		assert_eq!(board.hc_evaluate(), 0);
+
+		let _ = std::fs::remove_dir_all(folder);
+	}
+
+	#[test]
+	fn file_encoding_round_trips() {
+		let folder = temp_folder("tb_encoding_test");
+		std::fs::create_dir_all(&folder).unwrap();
+
+		let path = folder.join(format!("KRvK.{}", TB_FILE_EXTENSION));
+		let table = EndgameTable::new(&parse_material("KRvK").unwrap(), path.clone());
+
+		// Runs of every length, including ones longer than a single run can store and ones crossing block boundaries
+		let mut rng = Pcg64::seed_from_u64(32);
+		let mut values = vec![];
+		while values.len() < table.entry_count() {
+			let value = match rng.gen_range(0..3) {
+				0 => DRAW,
+				1 => win_in(rng.gen_range(1..64)),
+				_ => loss_in(rng.gen_range(0..64)),
+			};
+
+			let run = if rng.gen_bool(0.1) { rng.gen_range(200..3000) } else { rng.gen_range(1..10) };
+			values.extend(std::iter::repeat_n(value, run));
+		}
+		values.truncate(table.entry_count());
+
+		table.save(&values).unwrap();
+
+		let loaded = load_table(&folder, "KRvK");
+		let contents = loaded.contents().unwrap();
+		assert!((0..values.len()).all(|i| contents.get(i) == values[i]));
+
+		// The pieces are checked, so a table can't be loaded as a different one
+		let wrong_table = EndgameTable::new(&parse_material("KQvK").unwrap(), path);
+		assert!(wrong_table.load().is_none());
+
+		let _ = std::fs::remove_dir_all(folder);
+	}
+}
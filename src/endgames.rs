@@ -0,0 +1,238 @@
+/*
+Specialized evaluation for endings that the normal evaluation doesn't understand
+
+Every recognizer is keyed by a material signature (see pieces::material_key), and either replaces
+hc_evaluate completely (mating patterns like KBNvK, where the king has to be driven into the right corner),
+or returns a scale factor that gets multiplied into hc_evaluate's output (drawish endings like opposite colored bishops)
+
+The side before the "v" in the name is the strong side, and every ending is also registered with the colors swapped
+*/
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use crate::piece_square_tables::{PAWN_WORTH, BISHOP_WORTH, ROOK_WORTH, QUEEN_WORTH};
+use crate::utils::get_lsb;
+use crate::pieces::*;
+use crate::Board;
+
+// Big enough that the search always goes for it, but nowhere near a mate score
+pub const KNOWN_WIN_EVAL: i32 = 10000;
+
+// From the strong side's perspective
+pub type EvaluationFunction = fn(&Board, bool) -> i32;
+// 0.0 = dead draw, 1.0 = leave the evaluation alone
+pub type ScaleFunction = fn(&Board, bool) -> f32;
+
+#[derive(Copy, Clone)]
+pub enum EndgameFunction {
+	Evaluation(EvaluationFunction),
+	Scale(ScaleFunction),
+}
+
+#[derive(Copy, Clone)]
+pub struct Endgame {
+	pub function: EndgameFunction,
+	pub strong_is_white: bool,
+}
+
+pub struct Endgames {
+	pub endgames: HashMap<u64, Endgame>,
+}
+
+impl Endgames {
+	pub fn new() -> Self {
+		let mut endgames = Self {
+			endgames: HashMap::new(),
+		};
+
+		// Mating patterns
+		endgames.add("KBNvK", EndgameFunction::Evaluation(evaluate_kbnk));
+		endgames.add("KQvKR", EndgameFunction::Evaluation(evaluate_kqkr));
+		endgames.add("KQvKP", EndgameFunction::Evaluation(evaluate_kqkp));
+		endgames.add("KNNvK", EndgameFunction::Evaluation(|_, _| 0));
+
+		// Drawish endings
+		endgames.add("KRvKB", EndgameFunction::Scale(|_, _| 0.2));
+		endgames.add("KRvKN", EndgameFunction::Scale(|_, _| 0.3));
+		endgames.add_with_pawns("KBvK", 1..=8, 0..=0, EndgameFunction::Scale(scale_wrong_rook_pawn));
+		endgames.add_with_pawns("KvK", 1..=8, 0..=0, EndgameFunction::Scale(scale_wrong_rook_pawn));
+		endgames.add_with_pawns("KBvKB", 0..=8, 0..=8, EndgameFunction::Scale(scale_opposite_bishops));
+
+		endgames
+	}
+
+	pub fn add(&mut self, name: &str, function: EndgameFunction) {
+		let counts = parse_material(name).unwrap_or_else(|| panic!("Invalid endgame name: {}", name));
+		self.add_counts(counts, function);
+	}
+
+	// Registers the ending once for every pawn count in the ranges, because the pawns are part of the material signature
+	pub fn add_with_pawns(&mut self, name: &str, strong_pawns: RangeInclusive<u8>, weak_pawns: RangeInclusive<u8>, function: EndgameFunction) {
+		let counts = parse_material(name).unwrap_or_else(|| panic!("Invalid endgame name: {}", name));
+
+		for strong in strong_pawns {
+			for weak in weak_pawns.clone() {
+				let mut counts = counts;
+				counts[0][PAWN] = strong;
+				counts[1][PAWN] = weak;
+				self.add_counts(counts, function);
+			}
+		}
+	}
+
+	fn add_counts(&mut self, counts: [[u8; 6]; 2], function: EndgameFunction) {
+		// Symmetrical endings just get overwritten by the black version, which doesn't matter because they don't care who's strong
+		self.endgames.insert(material_key(&counts), Endgame { function, strong_is_white: true });
+		self.endgames.insert(material_key(&[counts[1], counts[0]]), Endgame { function, strong_is_white: false });
+	}
+
+	pub fn probe(&self, board: &Board) -> Option<Endgame> {
+		if let Some(endgame) = self.endgames.get(&board.material_key()) {
+			return Some(*endgame);
+		}
+
+		// A lone king against enough material to mate is the same for every material signature
+		for strong_is_white in [true, false] {
+			if board.color_bitboards[(!strong_is_white) as usize].count_ones() == 1
+			&& board.total_material_without_pawns[strong_is_white as usize] >= ROOK_WORTH {
+				return Some(Endgame { function: EndgameFunction::Evaluation(evaluate_kxk), strong_is_white });
+			}
+		}
+
+		None
+	}
+}
+
+pub fn distance(a: usize, b: usize) -> i32 {
+	usize::max((a / 8).abs_diff(b / 8), (a % 8).abs_diff(b % 8)) as i32
+}
+
+fn is_light_square(i: usize) -> bool {
+	(i / 8 + i % 8).is_multiple_of(2)
+}
+
+// 0 in the middle of the board, 6 in the corners
+fn center_distance(i: usize) -> i32 {
+	let rank = i / 8;
+	let file = i % 8;
+	(usize::max(rank, 7 - rank) + usize::max(file, 7 - file) - 8) as i32
+}
+
+fn push_to_edge(i: usize) -> i32 { center_distance(i) * 16 }
+fn push_close(a: usize, b: usize) -> i32 { 140 - distance(a, b) * 20 }
+
+fn king_square(board: &Board, white: bool) -> usize {
+	get_lsb(board.piece_bitboards[build_piece(white, KING)]) as usize
+}
+
+fn strong_material(board: &Board, strong_is_white: bool) -> i32 {
+	  board.total_material_without_pawns[strong_is_white as usize]
+	+ board.piece_bitboards[build_piece(strong_is_white, PAWN)].count_ones() as i32 * PAWN_WORTH
+}
+
+// Lone king: drive it to the edge, and bring the strong king closer to help
+fn evaluate_kxk(board: &Board, strong_is_white: bool) -> i32 {
+	let strong_king = king_square(board, strong_is_white);
+	let weak_king = king_square(board, !strong_is_white);
+
+	let mut evaluation = strong_material(board, strong_is_white) + push_to_edge(weak_king) + push_close(strong_king, weak_king);
+
+	let bishops = board.piece_bitboards[build_piece(strong_is_white, BISHOP)];
+	let light_bishops = (0..64).filter(|i| bishops & (1 << i) != 0 && is_light_square(*i)).count();
+
+	if board.piece_bitboards[build_piece(strong_is_white, QUEEN)] != 0
+	|| board.piece_bitboards[build_piece(strong_is_white, ROOK)] != 0
+	|| (bishops != 0 && board.piece_bitboards[build_piece(strong_is_white, KNIGHT)] != 0)
+	|| (light_bishops > 0 && light_bishops < bishops.count_ones() as usize) {
+		evaluation += KNOWN_WIN_EVAL;
+	}
+
+	evaluation
+}
+
+// Only the two corners of the bishop's color can be mated in, so the weak king gets pushed towards those
+fn evaluate_kbnk(board: &Board, strong_is_white: bool) -> i32 {
+	let strong_king = king_square(board, strong_is_white);
+	let weak_king = king_square(board, !strong_is_white);
+	let bishop = get_lsb(board.piece_bitboards[build_piece(strong_is_white, BISHOP)]) as usize;
+
+	// a8 and h1 are light, a1 and h8 are dark
+	let corners = if is_light_square(bishop) { [0, 63] } else { [56, 7] };
+	let corner_distance = corners.iter()
+		.map(|corner| ((weak_king / 8).abs_diff(corner / 8) + (weak_king % 8).abs_diff(corner % 8)) as i32)
+		.min()
+		.unwrap_or(0);
+
+	KNOWN_WIN_EVAL + strong_material(board, strong_is_white) + push_close(strong_king, weak_king) + (14 - corner_distance) * 40
+}
+
+fn evaluate_kqkr(board: &Board, strong_is_white: bool) -> i32 {
+	let strong_king = king_square(board, strong_is_white);
+	let weak_king = king_square(board, !strong_is_white);
+
+	QUEEN_WORTH - ROOK_WORTH + push_to_edge(weak_king) + push_close(strong_king, weak_king)
+}
+
+// A pawn on the 7th rank with its king next to it can hold a draw on the a, c, f and h files
+fn evaluate_kqkp(board: &Board, strong_is_white: bool) -> i32 {
+	let strong_king = king_square(board, strong_is_white);
+	let weak_king = king_square(board, !strong_is_white);
+	let pawn = get_lsb(board.piece_bitboards[build_piece(!strong_is_white, PAWN)]) as usize;
+
+	// The weak side's pawns move towards the strong side's back rank
+	let pawn_on_seventh = pawn / 8 == if strong_is_white { 6 } else { 1 };
+
+	let mut evaluation = push_close(strong_king, weak_king);
+
+	if !pawn_on_seventh
+	|| distance(weak_king, pawn) != 1
+	|| [1, 3, 4, 6].contains(&(pawn % 8)) {
+		evaluation += QUEEN_WORTH - PAWN_WORTH;
+	}
+
+	evaluation
+}
+
+// Rook pawns can't be promoted if the defending king gets in front of them,
+// unless there's a bishop that controls the promotion square
+fn scale_wrong_rook_pawn(board: &Board, strong_is_white: bool) -> f32 {
+	let pawns = board.piece_bitboards[build_piece(strong_is_white, PAWN)];
+	let weak_king = king_square(board, !strong_is_white);
+
+	for file in [0, 7] {
+		let file_bitboard = 0x0101010101010101 << file;
+		if pawns & !file_bitboard != 0 {
+			continue;
+		}
+
+		let promotion_square = if strong_is_white { file } else { 56 + file };
+
+		let bishops = board.piece_bitboards[build_piece(strong_is_white, BISHOP)];
+		if bishops != 0
+		&& is_light_square(get_lsb(bishops) as usize) == is_light_square(promotion_square) {
+			return 1.0;
+		}
+
+		if distance(weak_king, promotion_square) <= 1 {
+			return 0.0;
+		}
+	}
+
+	1.0
+}
+
+// Opposite colored bishops are really hard to win with, unless one side is up a lot of pawns
+fn scale_opposite_bishops(board: &Board, strong_is_white: bool) -> f32 {
+	let white_bishop = get_lsb(board.piece_bitboards[WHITE_BISHOP]) as usize;
+	let black_bishop = get_lsb(board.piece_bitboards[BLACK_BISHOP]) as usize;
+	if is_light_square(white_bishop) == is_light_square(black_bishop) {
+		return 1.0;
+	}
+
+	let pawn_difference = board.piece_bitboards[WHITE_PAWN].count_ones().abs_diff(board.piece_bitboards[BLACK_PAWN].count_ones());
+	match pawn_difference {
+		0 | 1 => 0.2,
+		2 => 0.5,
+		_ => 0.75,
+	}
+}
@@ -0,0 +1,312 @@
+/*
+Every weight of the handcrafted evaluation in one place, so a different set can be loaded at runtime instead of recompiling
+
+The defaults are the constants in piece_square_tables.rs, pawn_structure.rs and evaluation.rs
+Parameter files are plain text, one parameter per line, with scores written as middlegame and endgame pairs:
+	<name> <values...>
+Anything that's missing from the file keeps its default
+*/
+
+use std::io::Write;
+use crate::piece_square_tables::{PIECE_VALUES, PIECE_SQUARE_TABLES};
+use crate::pawn_structure::*;
+use crate::evaluation::*;
+use crate::score::{Score, s};
+
+#[derive(Clone)]
+pub struct EvalParams {
+	pub piece_values: [Score; 6],
+	// From white's perspective, indexed by piece type
+	pub piece_square_tables: [[Score; 64]; 6],
+
+	pub doubled_pawn_penalty: Score,
+	pub isolated_pawn_penalty: Score,
+	pub backward_pawn_penalty: Score,
+	pub supported_pawn_bonus: [Score; 8],
+	pub phalanx_pawn_bonus: [Score; 8],
+	pub candidate_passer_bonus: [Score; 8],
+	pub passed_pawn_boost: [Score; 8],
+
+	pub passed_rank_weight: [i32; 8],
+	pub passed_enemy_king_distance: Score,
+	pub passed_own_king_distance: Score,
+	pub passed_free_path_bonus: [Score; 8],
+	pub passed_safe_path_bonus: [Score; 8],
+	pub passed_blocked_penalty: [Score; 8],
+
+	pub knight_mobility: [Score; 9],
+	pub bishop_mobility: [Score; 14],
+	pub rook_mobility: [Score; 15],
+	pub queen_mobility: [Score; 28],
+
+	pub king_attack_weights: [i32; 6],
+	pub safe_check_units: [i32; 6],
+	pub pawn_shield_units: [i32; 3],
+	pub pawn_storm_units: [i32; 3],
+	pub half_open_file_units: i32,
+	pub open_file_units: i32,
+	pub safety_table: [i32; 100],
+
+	pub bishop_pair_bonus: Score,
+	pub rook_open_file_bonus: Score,
+	pub rook_half_open_file_bonus: Score,
+	pub queen_open_file_bonus: Score,
+	pub queen_half_open_file_bonus: Score,
+	pub rook_on_seventh_bonus: Score,
+	pub knight_outpost_bonus: Score,
+	pub bishop_outpost_bonus: Score,
+	pub bad_bishop_penalty: Score,
+	pub threat_by_pawn: [Score; 6],
+	pub hanging_piece_bonus: Score,
+}
+
+impl Default for EvalParams {
+	fn default() -> Self {
+		Self {
+			piece_values: PIECE_VALUES,
+			piece_square_tables: PIECE_SQUARE_TABLES,
+
+			doubled_pawn_penalty: DOUBLED_PAWN_PENALTY,
+			isolated_pawn_penalty: ISOLATED_PAWN_PENALTY,
+			backward_pawn_penalty: BACKWARD_PAWN_PENALTY,
+			supported_pawn_bonus: SUPPORTED_PAWN_BONUS,
+			phalanx_pawn_bonus: PHALANX_PAWN_BONUS,
+			candidate_passer_bonus: CANDIDATE_PASSER_BONUS,
+			passed_pawn_boost: PASSED_PAWN_BOOST,
+
+			passed_rank_weight: PASSED_RANK_WEIGHT,
+			passed_enemy_king_distance: PASSED_ENEMY_KING_DISTANCE,
+			passed_own_king_distance: PASSED_OWN_KING_DISTANCE,
+			passed_free_path_bonus: PASSED_FREE_PATH_BONUS,
+			passed_safe_path_bonus: PASSED_SAFE_PATH_BONUS,
+			passed_blocked_penalty: PASSED_BLOCKED_PENALTY,
+
+			knight_mobility: KNIGHT_MOBILITY,
+			bishop_mobility: BISHOP_MOBILITY,
+			rook_mobility: ROOK_MOBILITY,
+			queen_mobility: QUEEN_MOBILITY,
+
+			king_attack_weights: KING_ATTACK_WEIGHTS,
+			safe_check_units: SAFE_CHECK_UNITS,
+			pawn_shield_units: PAWN_SHIELD_UNITS,
+			pawn_storm_units: PAWN_STORM_UNITS,
+			half_open_file_units: HALF_OPEN_FILE_UNITS,
+			open_file_units: OPEN_FILE_UNITS,
+			safety_table: SAFETY_TABLE,
+
+			bishop_pair_bonus: BISHOP_PAIR_BONUS,
+			rook_open_file_bonus: ROOK_OPEN_FILE_BONUS,
+			rook_half_open_file_bonus: ROOK_HALF_OPEN_FILE_BONUS,
+			queen_open_file_bonus: QUEEN_OPEN_FILE_BONUS,
+			queen_half_open_file_bonus: QUEEN_HALF_OPEN_FILE_BONUS,
+			rook_on_seventh_bonus: ROOK_ON_SEVENTH_BONUS,
+			knight_outpost_bonus: KNIGHT_OUTPOST_BONUS,
+			bishop_outpost_bonus: BISHOP_OUTPOST_BONUS,
+			bad_bishop_penalty: BAD_BISHOP_PENALTY,
+			threat_by_pawn: THREAT_BY_PAWN,
+			hanging_piece_bonus: HANGING_PIECE_BONUS,
+		}
+	}
+}
+
+pub enum ParamValues<'a> {
+	Scores(&'a mut [Score]),
+	Integers(&'a mut [i32]),
+}
+
+impl ParamValues<'_> {
+	// Scores count as two numbers
+	pub fn len(&self) -> usize {
+		match self {
+			ParamValues::Scores(scores) => scores.len() * 2,
+			ParamValues::Integers(integers) => integers.len(),
+		}
+	}
+
+	pub fn get(&self, i: usize) -> i32 {
+		match self {
+			ParamValues::Scores(scores) => if i.is_multiple_of(2) { scores[i / 2].mg() } else { scores[i / 2].eg() },
+			ParamValues::Integers(integers) => integers[i],
+		}
+	}
+
+	pub fn set(&mut self, i: usize, value: i32) {
+		match self {
+			ParamValues::Scores(scores) => {
+				let score = scores[i / 2];
+				scores[i / 2] = if i.is_multiple_of(2) { s(value, score.eg()) } else { s(score.mg(), value) };
+			}
+			ParamValues::Integers(integers) => integers[i] = value,
+		}
+	}
+}
+
+impl EvalParams {
+	// An empty path (or "<empty>") means the defaults
+	pub fn open(path: &str) -> Self {
+		if path.is_empty()
+		|| path == "<empty>" {
+			return Self::default();
+		}
+
+		match Self::load(path) {
+			Ok(params) => params,
+			Err(error) => {
+				println!("info string Failed to load evaluation parameters from {}: {}", path, error);
+				Self::default()
+			}
+		}
+	}
+
+	pub fn load(path: &str) -> Result<Self, String> {
+		let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+		let mut params = Self::default();
+
+		{
+			let mut named_values = params.values();
+
+			for line in text.lines() {
+				let split = line.split_whitespace().collect::<Vec<&str>>();
+				if split.is_empty()
+				|| split[0].starts_with('#') {
+					continue;
+				}
+
+				let Some((_, values)) = named_values.iter_mut().find(|(name, _)| *name == split[0]) else {
+					return Err(format!("unknown parameter {}", split[0]));
+				};
+
+				if split.len() - 1 != values.len() {
+					return Err(format!("{} needs {} values, but has {}", split[0], values.len(), split.len() - 1));
+				}
+
+				for (i, value) in split[1..].iter().enumerate() {
+					let Ok(value) = value.parse::<i32>() else {
+						return Err(format!("{} isn't a number ({})", value, split[0]));
+					};
+
+					values.set(i, value);
+				}
+			}
+		}
+
+		Ok(params)
+	}
+
+	pub fn save(&self, path: &str) -> std::io::Result<()> {
+		let mut params = self.clone();
+
+		let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+		writeln!(file, "# Maxwell evaluation parameters: <name> <values...>, scores are written as middlegame endgame pairs")?;
+
+		for (name, values) in params.values() {
+			let values = (0..values.len()).map(|i| values.get(i).to_string()).collect::<Vec<String>>();
+			writeln!(file, "{} {}", name, values.join(" "))?;
+		}
+
+		file.flush()
+	}
+
+	// Every parameter by name, in the order they're saved in
+	pub fn values(&mut self) -> Vec<(&'static str, ParamValues<'_>)> {
+		let Self {
+			piece_values,
+			piece_square_tables,
+
+			doubled_pawn_penalty,
+			isolated_pawn_penalty,
+			backward_pawn_penalty,
+			supported_pawn_bonus,
+			phalanx_pawn_bonus,
+			candidate_passer_bonus,
+			passed_pawn_boost,
+
+			passed_rank_weight,
+			passed_enemy_king_distance,
+			passed_own_king_distance,
+			passed_free_path_bonus,
+			passed_safe_path_bonus,
+			passed_blocked_penalty,
+
+			knight_mobility,
+			bishop_mobility,
+			rook_mobility,
+			queen_mobility,
+
+			king_attack_weights,
+			safe_check_units,
+			pawn_shield_units,
+			pawn_storm_units,
+			half_open_file_units,
+			open_file_units,
+			safety_table,
+
+			bishop_pair_bonus,
+			rook_open_file_bonus,
+			rook_half_open_file_bonus,
+			queen_open_file_bonus,
+			queen_half_open_file_bonus,
+			rook_on_seventh_bonus,
+			knight_outpost_bonus,
+			bishop_outpost_bonus,
+			bad_bishop_penalty,
+			threat_by_pawn,
+			hanging_piece_bonus,
+		} = self;
+
+		let [pawn_table, knight_table, bishop_table, rook_table, queen_table, king_table] = piece_square_tables;
+
+		use ParamValues::{Scores, Integers};
+		use std::slice::from_mut as one;
+
+		vec![
+			("piece_values",               Scores(piece_values)),
+			("pawn_table",                 Scores(pawn_table)),
+			("knight_table",               Scores(knight_table)),
+			("bishop_table",               Scores(bishop_table)),
+			("rook_table",                 Scores(rook_table)),
+			("queen_table",                Scores(queen_table)),
+			("king_table",                 Scores(king_table)),
+
+			("doubled_pawn_penalty",       Scores(one(doubled_pawn_penalty))),
+			("isolated_pawn_penalty",      Scores(one(isolated_pawn_penalty))),
+			("backward_pawn_penalty",      Scores(one(backward_pawn_penalty))),
+			("supported_pawn_bonus",       Scores(supported_pawn_bonus)),
+			("phalanx_pawn_bonus",         Scores(phalanx_pawn_bonus)),
+			("candidate_passer_bonus",     Scores(candidate_passer_bonus)),
+			("passed_pawn_boost",          Scores(passed_pawn_boost)),
+
+			("passed_rank_weight",         Integers(passed_rank_weight)),
+			("passed_enemy_king_distance", Scores(one(passed_enemy_king_distance))),
+			("passed_own_king_distance",   Scores(one(passed_own_king_distance))),
+			("passed_free_path_bonus",     Scores(passed_free_path_bonus)),
+			("passed_safe_path_bonus",     Scores(passed_safe_path_bonus)),
+			("passed_blocked_penalty",     Scores(passed_blocked_penalty)),
+
+			("knight_mobility",            Scores(knight_mobility)),
+			("bishop_mobility",            Scores(bishop_mobility)),
+			("rook_mobility",              Scores(rook_mobility)),
+			("queen_mobility",             Scores(queen_mobility)),
+
+			("king_attack_weights",        Integers(king_attack_weights)),
+			("safe_check_units",           Integers(safe_check_units)),
+			("pawn_shield_units",          Integers(pawn_shield_units)),
+			("pawn_storm_units",           Integers(pawn_storm_units)),
+			("half_open_file_units",       Integers(one(half_open_file_units))),
+			("open_file_units",            Integers(one(open_file_units))),
+			("safety_table",               Integers(safety_table)),
+
+			("bishop_pair_bonus",          Scores(one(bishop_pair_bonus))),
+			("rook_open_file_bonus",       Scores(one(rook_open_file_bonus))),
+			("rook_half_open_file_bonus",  Scores(one(rook_half_open_file_bonus))),
+			("queen_open_file_bonus",      Scores(one(queen_open_file_bonus))),
+			("queen_half_open_file_bonus", Scores(one(queen_half_open_file_bonus))),
+			("rook_on_seventh_bonus",      Scores(one(rook_on_seventh_bonus))),
+			("knight_outpost_bonus",       Scores(one(knight_outpost_bonus))),
+			("bishop_outpost_bonus",       Scores(one(bishop_outpost_bonus))),
+			("bad_bishop_penalty",         Scores(one(bad_bishop_penalty))),
+			("threat_by_pawn",             Scores(threat_by_pawn)),
+			("hanging_piece_bonus",        Scores(one(hanging_piece_bonus))),
+		]
+	}
+}
@@ -0,0 +1,526 @@
+/*
+Maxwell's handcrafted evaluation
+
+Every term is a packed (middlegame, endgame) Score, and they're all added up for each side first,
+then blended together by the game phase at the end, so any term can be worth something different in each phase
+*/
+
+use crate::piece_square_tables::{flip_index, BISHOP_WORTH, ROOK_WORTH};
+use crate::endgames::EndgameFunction;
+use crate::score::{Score, s, PHASE_MAX};
+use crate::utils::{pop_lsb, get_lsb};
+use crate::pieces::*;
+use crate::Board;
+
+// Non-pawn material (for both sides together) above which it's a full middlegame, and below which it's a full endgame
+pub const MIDDLEGAME_MATERIAL: i32 = ((ROOK_WORTH * 2 + BISHOP_WORTH * 2) * 5) / 3;
+pub const ENDGAME_MATERIAL: i32 = ((ROOK_WORTH * 2 + BISHOP_WORTH * 2) * 5) / 9;
+
+// Indexed by the number of safe squares a piece can go to: not taken by its own pieces, and not attacked by enemy pawns
+pub const KNIGHT_MOBILITY: [Score; 9] = [
+	s(-49, -39), s(-42, -27), s(-10, -15), s( -3,  -8), s(  2,   2), s( 10,   5), s( 17,   8), s( 22,  10),
+	s( 26,  12),
+];
+
+pub const BISHOP_MOBILITY: [Score; 14] = [
+	s(-38, -28), s(-16, -11), s( 13,  -1), s( 21,   6), s( 30,  12), s( 40,  20), s( 44,  26), s( 50,  27),
+	s( 50,  31), s( 54,  35), s( 64,  38), s( 64,  41), s( 72,  42), s( 78,  47),
+];
+
+pub const ROOK_MOBILITY: [Score; 15] = [
+	s(-48, -38), s(-16,  -8), s(  2,  11), s(  2,  19), s(  2,  34), s(  9,  48), s( 17,  50), s( 25,  58),
+	s( 32,  64), s( 32,  67), s( 33,  76), s( 38,  79), s( 45,  81), s( 45,  81), s( 49,  83),
+];
+
+pub const QUEEN_MOBILITY: [Score; 28] = [
+	s(-24, -23), s(-10, -14), s( -6,  -3), s( -7,   9), s( 16,  19), s( 18,  26), s( 18,  28), s( 28,  36),
+	s( 30,  38), s( 42,  46), s( 51,  46), s( 52,  48), s( 52,  58), s( 52,  61), s( 53,  63), s( 53,  64),
+	s( 57,  65), s( 57,  68), s( 61,  71), s( 63,  72), s( 74,  73), s( 86,  81), s( 86,  81), s( 86,  82),
+	s( 87,  88), s( 90,  88), s( 90,  92), s( 92, 105),
+];
+
+pub const BISHOP_PAIR_BONUS: Score = s(25, 50);
+// No pawns on the file, or only enemy pawns
+pub const ROOK_OPEN_FILE_BONUS: Score = s(40, 18);
+pub const ROOK_HALF_OPEN_FILE_BONUS: Score = s(18, 8);
+pub const QUEEN_OPEN_FILE_BONUS: Score = s(6, 4);
+pub const QUEEN_HALF_OPEN_FILE_BONUS: Score = s(3, 2);
+// Only when there are enemy pawns on the 7th rank to go after, or it cuts the enemy king off on the 8th
+pub const ROOK_ON_SEVENTH_BONUS: Score = s(10, 28);
+// On the 4th to 6th rank, protected by a pawn, and no enemy pawns can ever attack it
+pub const KNIGHT_OUTPOST_BONUS: Score = s(30, 20);
+pub const BISHOP_OUTPOST_BONUS: Score = s(18, 10);
+// Per pawn on the bishop's square color, and again if that pawn can't move
+pub const BAD_BISHOP_PENALTY: Score = s(2, 5);
+// Per enemy piece attacked by a pawn, by piece type
+pub const THREAT_BY_PAWN: [Score; 6] = [s(0, 0), s(45, 25), s(45, 25), s(60, 30), s(55, 40), s(0, 0)];
+// Per enemy piece (not pawns) that's attacked and not defended at all
+pub const HANGING_PIECE_BONUS: Score = s(35, 18);
+
+pub const LIGHT_SQUARES: u64 = 0xAA55AA55AA55AA55;
+
+// King safety counts "attack units" against each king, and then looks up how bad that many units is in SAFETY_TABLE,
+// so a lot of small threats together are much worse than each one on its own
+
+// Per attacking piece type that attacks the king zone
+pub const KING_ATTACK_WEIGHTS: [i32; 6] = [0, 2, 2, 3, 5, 0];
+// Per piece type that has a safe square to give check from
+pub const SAFE_CHECK_UNITS: [i32; 6] = [0, 3, 2, 4, 6, 0];
+// Per file next to the king: the closest shield pawn is on the 2nd rank, 3rd rank, or missing
+pub const PAWN_SHIELD_UNITS: [i32; 3] = [0, 1, 3];
+// Per file next to the king: an enemy pawn 1, 2 or 3 ranks in front of the king
+pub const PAWN_STORM_UNITS: [i32; 3] = [3, 2, 1];
+pub const HALF_OPEN_FILE_UNITS: i32 = 2;
+pub const OPEN_FILE_UNITS: i32 = 3;
+
+pub const SAFETY_TABLE: [i32; 100] = [
+	  0,   0,   1,   2,   3,   5,   7,   9,  12,  15,
+	 18,  22,  26,  30,  35,  39,  44,  50,  56,  62,
+	 68,  75,  82,  85,  89,  97, 105, 113, 122, 131,
+	140, 150, 169, 180, 191, 202, 213, 225, 237, 248,
+	260, 272, 283, 295, 307, 319, 330, 342, 354, 366,
+	377, 389, 401, 412, 424, 436, 448, 459, 471, 483,
+	494, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+	500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+	500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+	500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+];
+
+pub fn file_bitboard(file: usize) -> u64 { 0x0101010101010101 << file }
+
+// 0 = the color's back rank
+pub fn relative_rank(i: usize, white: bool) -> usize {
+	if white { 7 - i / 8 } else { i / 8 }
+}
+
+// Everything that's attacked on the board, calculated once per evaluation so every term can use it
+// All arrays are indexed by color (white = 1)
+pub struct AttackInfo {
+	// By piece type
+	pub attacked_by: [[u64; 6]; 2],
+	pub attacked: [u64; 2],
+	// Squares attacked by at least two pieces
+	pub attacked_twice: [u64; 2],
+
+	// The king's square and everything around it
+	pub king_zone: [u64; 2],
+	// Indexed by the king's color: how many pieces attack its zone, how much they weigh together, and how many zone squares they attack
+	pub king_attackers: [i32; 2],
+	pub king_attack_weight: [i32; 2],
+	pub king_zone_attacks: [i32; 2],
+
+	pub mobility: [Score; 2],
+}
+
+// Every term of the evaluation is added up separately, so the eval command can show where the evaluation comes from
+#[derive(Copy, Clone)]
+pub enum EvalTerm {
+	Material,
+	PieceSquareTables,
+	PawnStructure,
+	PassedPawns,
+	Mobility,
+	KingSafety,
+	Pieces,
+	Threats,
+}
+
+pub const EVAL_TERM_COUNT: usize = 8;
+pub const EVAL_TERM_NAMES: [&str; EVAL_TERM_COUNT] = [
+	"Material",
+	"Piece square tables",
+	"Pawn structure",
+	"Passed pawns",
+	"Mobility",
+	"King safety",
+	"Pieces",
+	"Threats",
+];
+
+#[derive(Default)]
+pub struct EvalTrace {
+	// By term, then by color (white = 1)
+	pub terms: [[Score; 2]; EVAL_TERM_COUNT],
+	pub phase: i32,
+	// When a specialized endgame or the tablebases evaluate the position by themselves, none of the terms are used
+	pub specialized_evaluation: Option<i32>,
+	pub tablebase_evaluation: Option<i32>,
+	pub scale: Option<f32>,
+	// From white's perspective
+	pub evaluation: i32,
+}
+
+impl EvalTrace {
+	pub fn add(&mut self, term: EvalTerm, white: bool, score: Score) {
+		self.terms[term as usize][white as usize] += score;
+	}
+
+	// White minus black
+	pub fn total(&self) -> Score {
+		self.terms.iter().fold(Score::ZERO, |total, [black, white]| total + *white - *black)
+	}
+
+	pub fn print(&self) {
+		if let Some(evaluation) = self.tablebase_evaluation {
+			println!("Tablebase evaluation: {}", evaluation);
+			return;
+		}
+
+		if let Some(evaluation) = self.specialized_evaluation {
+			println!("Specialized endgame evaluation: {}", evaluation);
+			return;
+		}
+
+		println!("{:<20} |     White     |     Black     |     Total     | Blended", "Term");
+		println!("{:<20} |    MG     EG  |    MG     EG  |    MG     EG  |", "");
+		println!("{}", "-".repeat(76));
+
+		for (name, [black, white]) in EVAL_TERM_NAMES.iter().zip(self.terms.iter()) {
+			self.print_row(name, *white, *black);
+		}
+
+		println!("{}", "-".repeat(76));
+		let [black, white] = self.terms.iter().fold([Score::ZERO; 2], |total, [black, white]| [total[0] + *black, total[1] + *white]);
+		self.print_row("Total", white, black);
+
+		println!();
+		println!("Phase: {}/{}", self.phase, PHASE_MAX);
+		if let Some(scale) = self.scale {
+			println!("Scaled by {:.2} (specialized endgame)", scale);
+		}
+		println!("Evaluation: {} (white's perspective)", self.evaluation);
+	}
+
+	fn print_row(&self, name: &str, white: Score, black: Score) {
+		let total = white - black;
+		println!("{:<20} | {:>6} {:>6} | {:>6} {:>6} | {:>6} {:>6} | {:>6}",
+			name,
+			white.mg(), white.eg(),
+			black.mg(), black.eg(),
+			total.mg(), total.eg(),
+			total.taper(self.phase),
+		);
+	}
+}
+
+impl Board {
+	// PHASE_MAX in the middlegame, down to 0 in the endgame
+	pub fn game_phase(&self) -> i32 {
+		let material = self.total_material_without_pawns.iter().sum::<i32>();
+		((material - ENDGAME_MATERIAL) * PHASE_MAX / (MIDDLEGAME_MATERIAL - ENDGAME_MATERIAL)).clamp(0, PHASE_MAX)
+	}
+
+	pub fn hc_evaluate(&mut self) -> i32 {
+		self.trace_evaluation().evaluation * self.perspective()
+	}
+
+	// From the side to move's perspective, for positions in Maxwell's own tablebases
+	pub fn tablebase_evaluate(&self) -> Option<i32> {
+		self.tablebases.as_ref()?.evaluate(self)
+	}
+
+	pub fn trace_evaluation(&mut self) -> EvalTrace {
+		let mut trace = EvalTrace {
+			phase: self.game_phase(),
+			..Default::default()
+		};
+
+		if let Some(evaluation) = self.tablebase_evaluate() {
+			trace.evaluation = evaluation * self.perspective();
+			trace.tablebase_evaluation = Some(trace.evaluation);
+			return trace;
+		}
+
+		let specialized_endgame = self.endgames.probe(self);
+		if let Some(specialized_endgame) = specialized_endgame {
+			if let EndgameFunction::Evaluation(evaluate) = specialized_endgame.function {
+				let evaluation = evaluate(self, specialized_endgame.strong_is_white);
+				trace.evaluation = if specialized_endgame.strong_is_white { evaluation } else { -evaluation };
+				trace.specialized_evaluation = Some(trace.evaluation);
+				return trace;
+			}
+		}
+
+		let info = self.attack_info();
+		let pawn_entry = self.pawn_structure();
+
+		for white in [true, false] {
+			trace.add(EvalTerm::PawnStructure, white, pawn_entry.score[white as usize]);
+			self.evaluate_color(white, &info, pawn_entry.passed[white as usize], &mut trace);
+		}
+
+		let mut evaluation = trace.total().taper(trace.phase);
+
+		if let Some(specialized_endgame) = specialized_endgame {
+			if let EndgameFunction::Scale(scale) = specialized_endgame.function {
+				let scale = scale(self, specialized_endgame.strong_is_white);
+				trace.scale = Some(scale);
+				evaluation = (evaluation as f32 * scale) as i32;
+			}
+		}
+
+		trace.evaluation = evaluation;
+		trace
+	}
+
+	// Everything from one side's point of view, except for the cached pawn structure
+	fn evaluate_color(&self, white: bool, info: &AttackInfo, passed: u64, trace: &mut EvalTrace) {
+		let params = &self.eval_params;
+		let mut material = Score::ZERO;
+		let mut piece_square_tables = Score::ZERO;
+		let mut pieces = Score::ZERO;
+
+		let color = white as usize;
+
+		let pawns = self.piece_bitboards[build_piece(white, PAWN)];
+		let enemy_pawns = self.piece_bitboards[build_piece(!white, PAWN)];
+		let occupied = self.color_bitboards[0] | self.color_bitboards[1];
+		let blocked_pawns = if white { pawns & occupied << 8 } else { pawns & occupied >> 8 };
+
+		for piece_type in PAWN..=KING {
+			let piece = build_piece(white, piece_type);
+
+			let mut bitboard = self.piece_bitboards[piece];
+			while bitboard != 0 {
+				let piece_index = pop_lsb(&mut bitboard) as usize;
+				let square = 1 << piece_index;
+				let rank = relative_rank(piece_index, white);
+				let file_mask = file_bitboard(piece_index % 8);
+
+				material += params.piece_values[piece_type];
+				piece_square_tables += params.piece_square_tables[piece_type][if white { piece_index } else { flip_index(piece_index) }];
+
+				if (piece_type == KNIGHT
+				|| piece_type == BISHOP)
+				&& (3..=5).contains(&rank)
+				&& info.attacked_by[color][PAWN] & square != 0
+				&& self.precalculated_move_data.squares_ahead_of_pawn[color][piece_index] & !file_mask & enemy_pawns == 0 {
+					pieces += if piece_type == KNIGHT { params.knight_outpost_bonus } else { params.bishop_outpost_bonus };
+				}
+
+				if piece_type == BISHOP {
+					let same_color_squares = if LIGHT_SQUARES & square != 0 { LIGHT_SQUARES } else { !LIGHT_SQUARES };
+					let bad_pawns = (pawns & same_color_squares).count_ones() + (blocked_pawns & same_color_squares).count_ones();
+					pieces -= params.bad_bishop_penalty * bad_pawns as i32;
+				}
+
+				if (piece_type == ROOK
+				|| piece_type == QUEEN)
+				&& pawns & file_mask == 0 {
+					pieces += match (piece_type, enemy_pawns & file_mask == 0) {
+						(ROOK, true)  => params.rook_open_file_bonus,
+						(ROOK, false) => params.rook_half_open_file_bonus,
+						(_, true)     => params.queen_open_file_bonus,
+						(_, false)    => params.queen_half_open_file_bonus,
+					};
+				}
+
+				if piece_type == ROOK
+				&& rank == 6 {
+					let enemy_king_rank = relative_rank(get_lsb(self.piece_bitboards[build_piece(!white, KING)]) as usize, white);
+					let seventh_rank = 0xFF << (piece_index / 8 * 8);
+
+					if enemy_pawns & seventh_rank != 0
+					|| enemy_king_rank == 7 {
+						pieces += params.rook_on_seventh_bonus;
+					}
+				}
+			}
+		}
+
+		if self.piece_bitboards[build_piece(white, BISHOP)].count_ones() >= 2 {
+			pieces += params.bishop_pair_bonus;
+		}
+
+		trace.add(EvalTerm::Material, white, material);
+		trace.add(EvalTerm::PieceSquareTables, white, piece_square_tables);
+		trace.add(EvalTerm::PassedPawns, white, self.evaluate_passed_pawns(white, passed, info));
+		trace.add(EvalTerm::Mobility, white, info.mobility[color]);
+		trace.add(EvalTerm::KingSafety, white, -self.king_danger(white, info));
+		trace.add(EvalTerm::Pieces, white, pieces);
+		trace.add(EvalTerm::Threats, white, self.threats(white, info));
+	}
+
+	// Enemy pieces this side is threatening
+	pub fn threats(&self, white: bool, info: &AttackInfo) -> Score {
+		let params = &self.eval_params;
+		let mut score = Score::ZERO;
+
+		let color = white as usize;
+		let enemy = 1 - color;
+
+		for piece_type in KNIGHT..=QUEEN {
+			let enemy_pieces = self.piece_bitboards[build_piece(!white, piece_type)];
+
+			score += params.threat_by_pawn[piece_type] * (enemy_pieces & info.attacked_by[color][PAWN]).count_ones() as i32;
+			score += params.hanging_piece_bonus * (enemy_pieces & info.attacked[color] & !info.attacked[enemy]).count_ones() as i32;
+		}
+
+		score
+	}
+
+	pub fn pawn_attacks_bitboard(&self, white: bool) -> u64 {
+		let mut attacks = 0;
+
+		let mut pawns = self.piece_bitboards[build_piece(white, PAWN)];
+		while pawns != 0 {
+			attacks |= self.precalculated_move_data.pawn_attacks[white as usize][pop_lsb(&mut pawns) as usize];
+		}
+
+		attacks
+	}
+
+	pub fn attack_info(&self) -> AttackInfo {
+		let params = &self.eval_params;
+		let mut info = AttackInfo {
+			attacked_by: [[0; 6]; 2],
+			attacked: [0; 2],
+			attacked_twice: [0; 2],
+
+			king_zone: [0; 2],
+			king_attackers: [0; 2],
+			king_attack_weight: [0; 2],
+			king_zone_attacks: [0; 2],
+
+			mobility: [Score::ZERO; 2],
+		};
+
+		for color in 0..2 {
+			let king_index = get_lsb(self.piece_bitboards[build_piece(color == 1, KING)]) as usize;
+			info.king_zone[color] = self.precalculated_move_data.king_attacks[king_index] | 1 << king_index;
+
+			info.attacked_by[color][PAWN] = self.pawn_attacks_bitboard(color == 1);
+			info.attacked_by[color][KING] = self.precalculated_move_data.king_attacks[king_index];
+
+			info.attacked_twice[color] = info.attacked_by[color][PAWN] & info.attacked_by[color][KING];
+			info.attacked[color] = info.attacked_by[color][PAWN] | info.attacked_by[color][KING];
+		}
+
+		for color in 0..2 {
+			let white = color == 1;
+			let enemy = 1 - color;
+
+			// Safe squares: not taken by its own pieces, and not attacked by enemy pawns
+			let mobility_area = !self.color_bitboards[color] & !info.attacked_by[enemy][PAWN];
+
+			for piece_type in KNIGHT..=QUEEN {
+				let mut bitboard = self.piece_bitboards[build_piece(white, piece_type)];
+				while bitboard != 0 {
+					let piece_index = pop_lsb(&mut bitboard) as usize;
+
+					let attacks = match piece_type {
+						KNIGHT => self.precalculated_move_data.knight_attacks[piece_index],
+						BISHOP => self.calculate_bishop_attack_bitboard(piece_index),
+						ROOK   => self.calculate_rook_attack_bitboard(piece_index),
+						_      => self.calculate_queen_attack_bitboard(piece_index),
+					};
+
+					info.attacked_by[color][piece_type] |= attacks;
+					info.attacked_twice[color] |= info.attacked[color] & attacks;
+					info.attacked[color] |= attacks;
+
+					let king_zone_attacks = attacks & info.king_zone[enemy];
+					if king_zone_attacks != 0 {
+						info.king_attackers[enemy] += 1;
+						info.king_attack_weight[enemy] += params.king_attack_weights[piece_type];
+						info.king_zone_attacks[enemy] += king_zone_attacks.count_ones() as i32;
+					}
+
+					let mobility = (attacks & mobility_area).count_ones() as usize;
+					info.mobility[color] += match piece_type {
+						KNIGHT => params.knight_mobility[mobility],
+						BISHOP => params.bishop_mobility[mobility],
+						ROOK   => params.rook_mobility[mobility],
+						_      => params.queen_mobility[mobility],
+					};
+				}
+			}
+		}
+
+		info
+	}
+
+	// The attack units against this color's king
+	pub fn king_attack_units(&self, white: bool, info: &AttackInfo) -> i32 {
+		let params = &self.eval_params;
+		let color = white as usize;
+		let enemy = 1 - color;
+
+		let king_index = get_lsb(self.piece_bitboards[build_piece(white, KING)]) as usize;
+		let king_file = king_index % 8;
+		let king_rank = relative_rank(king_index, white);
+
+		let pawns = self.piece_bitboards[build_piece(white, PAWN)];
+		let enemy_pawns = self.piece_bitboards[build_piece(!white, PAWN)];
+		let enemy_has_queen = self.piece_bitboards[build_piece(!white, QUEEN)] != 0;
+
+		let mut units = 0;
+
+		// One piece on its own can't do much, unless it's the queen
+		if info.king_attackers[color] >= 2
+		|| (info.king_attackers[color] >= 1 && enemy_has_queen) {
+			units += info.king_attack_weight[color] + info.king_zone_attacks[color];
+
+			// Squares the enemy can check from without getting taken
+			let safe = !info.attacked[color] & !self.color_bitboards[enemy];
+			let bishop_lines = self.calculate_bishop_attack_bitboard(king_index);
+			let rook_lines = self.calculate_rook_attack_bitboard(king_index);
+
+			let check_squares = [
+				0,
+				self.precalculated_move_data.knight_attacks[king_index],
+				bishop_lines,
+				rook_lines,
+				bishop_lines | rook_lines,
+			];
+
+			for (piece_type, squares) in check_squares.iter().enumerate().skip(KNIGHT) {
+				if squares & info.attacked_by[enemy][piece_type] & safe != 0 {
+					units += params.safe_check_units[piece_type];
+				}
+			}
+		}
+
+		// Pawn shield, pawn storms and open files, on the king's file and the files next to it
+		for file in king_file.saturating_sub(1)..=usize::min(7, king_file + 1) {
+			let file_mask = file_bitboard(file);
+
+			let mut shield_distance = 3;
+			let mut storm_distance = 4;
+
+			let mut file_pawns = (pawns | enemy_pawns) & file_mask;
+			while file_pawns != 0 {
+				let i = pop_lsb(&mut file_pawns) as usize;
+				let rank = relative_rank(i, white);
+				if rank <= king_rank {
+					continue;
+				}
+
+				let distance = rank - king_rank;
+				if pawns & 1 << i != 0 {
+					shield_distance = usize::min(shield_distance, distance);
+				} else {
+					storm_distance = usize::min(storm_distance, distance);
+				}
+			}
+
+			units += params.pawn_shield_units[usize::min(shield_distance, 3) - 1];
+			if storm_distance <= 3 {
+				units += params.pawn_storm_units[storm_distance - 1];
+			}
+
+			if pawns & file_mask == 0 {
+				units += if enemy_pawns & file_mask == 0 { params.open_file_units } else { params.half_open_file_units };
+			}
+		}
+
+		units
+	}
+
+	// Only a middlegame penalty, in the endgame the king wants to walk up the board
+	pub fn king_danger(&self, white: bool, info: &AttackInfo) -> Score {
+		let params = &self.eval_params;
+		let units = self.king_attack_units(white, info);
+		s(params.safety_table[units.clamp(0, params.safety_table.len() as i32 - 1) as usize], 0)
+	}
+}
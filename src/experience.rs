@@ -0,0 +1,175 @@
+/*
+The experience file keeps the results of deep searches between sessions, so a ucinewgame (or restarting Maxwell)
+doesn't throw away long analysis, and openings that come up again don't have to be searched from scratch
+
+After every search, the root position's best move, depth and evaluation are recorded (if the search was deep enough),
+and when a Bot is created they're all stored into the transposition table as exact entries
+
+One position per line:
+	<zobrist key in hex> <best move, MoveData::to_binary in hex> <depth> <evaluation>
+*/
+
+use std::collections::HashMap;
+use std::io::Write;
+use crate::move_data::MoveData;
+use crate::transposition_table::{TranspositionTable, EvalBound};
+
+// Shallower searches are cheap enough to redo, and would just fill the file up
+pub const EXPERIENCE_MIN_DEPTH: u8 = 10;
+pub const DEFAULT_EXPERIENCE_SIZE: usize = 100000;
+
+#[derive(Copy, Clone)]
+pub struct ExperienceEntry {
+	pub best_move: u16,
+	pub depth: u8,
+	pub evaluation: i32,
+}
+
+pub struct Experience {
+	pub path: String,
+	pub max_entries: usize,
+	pub entries: HashMap<u64, ExperienceEntry>,
+}
+
+impl Experience {
+	pub fn open(path: &str, max_entries: usize) -> Self {
+		let mut experience = Self {
+			path: String::new(),
+			max_entries,
+			entries: HashMap::new(),
+		};
+
+		if path.is_empty()
+		|| path == "<empty>" {
+			return experience;
+		}
+
+		experience.path = path.to_string();
+		// There's no file until the first save
+		experience.entries = Self::read(path).unwrap_or_default();
+
+		experience
+	}
+
+	pub fn is_open(&self) -> bool { !self.path.is_empty() }
+
+	fn read(path: &str) -> Option<HashMap<u64, ExperienceEntry>> {
+		let text = std::fs::read_to_string(path).ok()?;
+		let mut entries = HashMap::new();
+
+		for line in text.lines() {
+			let split = line.trim().split(' ').collect::<Vec<&str>>();
+			if split.len() != 4
+			|| split[0].starts_with('#') {
+				continue;
+			}
+
+			if let (Ok(key), Ok(best_move), Ok(depth), Ok(evaluation)) = (
+				u64::from_str_radix(split[0], 16),
+				u16::from_str_radix(split[1], 16),
+				split[2].parse::<u8>(),
+				split[3].parse::<i32>(),
+			) {
+				entries.insert(key, ExperienceEntry { best_move, depth, evaluation });
+			}
+		}
+
+		Some(entries)
+	}
+
+	// Deeper results replace shallower ones, and the newest result wins on the same depth
+	pub fn record(&mut self, key: u64, best_move: MoveData, depth: u8, evaluation: i32) {
+		if !self.is_open()
+		|| depth < EXPERIENCE_MIN_DEPTH {
+			return;
+		}
+
+		let entry = ExperienceEntry { best_move: best_move.to_binary(), depth, evaluation };
+		if self.entries.get(&key).is_none_or(|old| old.depth <= depth) {
+			self.entries.insert(key, entry);
+		}
+	}
+
+	// Same rule as record, but for every entry of another experience file
+	pub fn merge(&mut self, other: &HashMap<u64, ExperienceEntry>) {
+		for (key, entry) in other.iter() {
+			if self.entries.get(key).is_none_or(|old| old.depth < entry.depth) {
+				self.entries.insert(*key, *entry);
+			}
+		}
+	}
+
+	pub fn merge_file(&mut self, path: &str) -> bool {
+		let Some(other) = Self::read(path) else {
+			return false;
+		};
+
+		self.merge(&other);
+		true
+	}
+
+	// Throws out entries that are too shallow, and then the shallowest ones until the file fits in max_entries
+	pub fn compact(&mut self) {
+		self.entries.retain(|_, entry| entry.depth >= EXPERIENCE_MIN_DEPTH);
+
+		if self.entries.len() > self.max_entries {
+			let mut depths = self.entries.values().map(|entry| entry.depth).collect::<Vec<u8>>();
+			depths.sort_unstable_by(|a, b| b.cmp(a));
+			let min_depth = depths[self.max_entries];
+
+			// Entries on the cutoff depth are dropped until it fits
+			let mut over = self.entries.len() - self.max_entries;
+			self.entries.retain(|_, entry| {
+				if entry.depth < min_depth {
+					over -= 1;
+					return false;
+				}
+
+				if entry.depth == min_depth
+				&& over > 0 {
+					over -= 1;
+					return false;
+				}
+
+				true
+			});
+		}
+	}
+
+	pub fn preload(&self, transposition_table: &mut TranspositionTable) {
+		for (key, entry) in self.entries.iter() {
+			// The root is at ply 0, so mate scores don't need fixing
+			transposition_table.store(*key, entry.depth, 0, entry.evaluation, MoveData::from_binary(entry.best_move), EvalBound::Exact);
+		}
+	}
+
+	// Merges with what's already in the file first, in case another Maxwell wrote to it
+	pub fn save(&mut self) {
+		if !self.is_open() {
+			return;
+		}
+
+		if let Some(on_disk) = Self::read(&self.path) {
+			self.merge(&on_disk);
+		}
+		self.compact();
+
+		if let Err(error) = self.write() {
+			println!("info string Failed to save experience to {}: {}", self.path, error);
+		}
+	}
+
+	fn write(&self) -> std::io::Result<()> {
+		let mut entries = self.entries.iter().collect::<Vec<_>>();
+		entries.sort_by_key(|(key, _)| **key);
+
+		let mut file = std::io::BufWriter::new(std::fs::File::create(&self.path)?);
+		writeln!(file, "# Maxwell experience: <zobrist key> <best move> <depth> <evaluation>")?;
+
+		for (key, entry) in entries {
+			writeln!(file, "{:016x} {:04x} {} {}", key, entry.best_move, entry.depth, entry.evaluation)?;
+		}
+
+		file.flush()
+	}
+}
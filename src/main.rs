@@ -8,18 +8,36 @@ mod log;
 mod value_holder;
 mod pieces;
 mod castling_rights;
+mod score;
 mod piece_square_tables;
 mod precalculated_move_data;
 mod magic_numbers;
 mod move_data;
+mod move_list;
 mod transposition_table;
 mod killer_moves;
 mod opening_book;
+mod syzygy;
+mod polyglot;
+mod polyglot_book;
+mod pgn;
+mod book_maker;
+mod book_learning;
+mod eco;
+mod experience;
+mod endgame_tablebase;
+mod endgames;
+mod evaluation;
+mod pawn_structure;
+mod eval_params;
+mod tuner;
 mod board;
 mod zobrist;
 mod perft;
+mod bench;
 mod bot;
 mod move_sorter;
+mod move_picker;
 mod scored_move_list;
 mod nnue;
 mod nnue_weights;
@@ -29,15 +47,28 @@ use std::io::Read;
 use std::io::Write;
 use rand::prelude::SliceRandom;
 use crate::nnue::NNUE;
+use crate::syzygy::Syzygy;
+use crate::polyglot::polyglot_key;
+use crate::polyglot_book::PolyglotBook;
+use crate::opening_book::OpeningBook;
+use crate::book_maker::{BookMakerConfig, make_book};
+use crate::book_learning::BookLearning;
+use crate::pgn::{parse_result, write_game};
+use crate::experience::Experience;
+use crate::eval_params::EvalParams;
+use crate::tuner::{TunerConfig, tune};
+use crate::endgame_tablebase::EndgameTablebases;
 use crate::utils::move_str_is_valid;
 use crate::castling_rights::print_castling_rights;
 use crate::bot::{Bot, BotConfig, MAX_DEPTH};
 use crate::perft::*;
+use crate::bench::{bench, BENCH_DEPTH};
 use crate::move_data::{MoveData};
 use crate::pieces::*;
 use crate::board::Board;
 use std::io;
 use std::time::Instant;
+use std::sync::Arc;
 // use colored::Colorize;
 // use crate::log::Log;
 
@@ -57,10 +88,13 @@ fn main() {
 	// let mut log = Log::none();
 
 	let mut board = Board::from_fen(&bot_config.fen);
+	// The position that "position" commands play their moves from, so the board only gets rebuilt when it changes
+	let mut root_fen = bot_config.fen.clone();
 	let mut bot = Bot::new(bot_config.clone());
+	board.set_eval_params(bot.eval_params.clone());
+	board.tablebases = Some(Arc::clone(&bot.tablebases));
 
 	let mut command = String::new();
-	let mut moves = String::new();
 
 	loop {
 		command.clear();
@@ -83,6 +117,15 @@ fn main() {
 				println!("id name Maxwell v3.1-3");
 				println!("id author eboatwright");
 				println!("option name Hash type spin default 256 min 0 max 4000");
+				println!("option name QSearchChecks type check default false");
+				println!("option name SyzygyPath type string default <empty>");
+				println!("option name OwnBook type check default false");
+				println!("option name BookFile type string default <empty>");
+				println!("option name BookBestMove type check default false");
+				println!("option name BookLearning type check default true");
+				println!("option name ExperienceFile type string default <empty>");
+				println!("option name EvalFile type string default <empty>");
+				println!("option name Evaluation type combo default HCE var HCE var NNUE");
 
 				println!("uciok");
 			}
@@ -98,6 +141,64 @@ fn main() {
 								bot_config.hash_size = value.parse::<usize>().unwrap_or(256);
 							}
 
+							"QSearchChecks" => {
+								bot_config.qsearch_checks = *value == "true";
+								bot.config.qsearch_checks = bot_config.qsearch_checks;
+							}
+
+							"SyzygyPath" => {
+								// Paths can have spaces in them
+								bot_config.syzygy_path = command_split[4..].join(" ");
+								bot.config.syzygy_path = bot_config.syzygy_path.clone();
+								bot.syzygy = Syzygy::new(&bot_config.syzygy_path);
+							}
+
+							"OwnBook" => {
+								bot_config.opening_book = *value == "true";
+								bot.config.opening_book = bot_config.opening_book;
+							}
+
+							"BookFile" => {
+								bot_config.book_file = command_split[4..].join(" ");
+								bot.config.book_file = bot_config.book_file.clone();
+								bot.polyglot_book = PolyglotBook::open(&bot_config.book_file);
+								bot.opening_book = OpeningBook::open(&bot_config.book_file);
+								bot.book_learning = BookLearning::open(&bot_config.book_file);
+							}
+
+							"BookBestMove" => {
+								bot_config.book_best_move = *value == "true";
+								bot.config.book_best_move = bot_config.book_best_move;
+							}
+
+							"BookLearning" => {
+								bot_config.book_learning = *value == "true";
+								bot.config.book_learning = bot_config.book_learning;
+							}
+
+							"ExperienceFile" => {
+								// Whatever the old file learned gets saved before switching
+								bot.experience.save();
+
+								bot_config.experience_file = command_split[4..].join(" ");
+								bot.config.experience_file = bot_config.experience_file.clone();
+								bot.experience = Experience::open(&bot_config.experience_file, bot_config.experience_size);
+								bot.experience.preload(&mut bot.transposition_table);
+							}
+
+							"Evaluation" => {
+								bot_config.nnue = *value == "NNUE";
+								bot.config.nnue = bot_config.nnue;
+							}
+
+							"EvalFile" => {
+								bot_config.eval_file = command_split[4..].join(" ");
+								bot.config.eval_file = bot_config.eval_file.clone();
+								bot.eval_params = EvalParams::open(&bot_config.eval_file);
+								board.set_eval_params(bot.eval_params.clone());
+								board.tablebases = Some(Arc::clone(&bot.tablebases));
+							}
+
 							_ => {}
 						}
 					}
@@ -109,22 +210,41 @@ fn main() {
 			"ucinewgame" => {
 				// log = Log::new();
 				board = Board::from_fen(STARTING_FEN);
+				root_fen = STARTING_FEN.to_string();
+				// The new Bot loads it back into its transposition table
+				bot.experience.save();
 				bot = Bot::new(bot_config.clone());
+				board.set_eval_params(bot.eval_params.clone());
+				board.tablebases = Some(Arc::clone(&bot.tablebases));
 			}
 
-			// TODO: add support for "position fen"
 			// Format: position startpos (moves e2e4 e7e5 ...)
+			//     or: position fen <FEN> (moves e2e4 e7e5 ...)
 			"position" => {
-				// Reset the board to the initial position
-				for _ in 0..board.moves.len() {
-					board.undo_last_move();
-				}
+				let moves_index = command_split.iter()
+					.position(|word| *word == "moves")
+					.unwrap_or(command_split.len());
+
+				let fen =
+					if command_split.get(1) == Some(&"fen") {
+						command_split[2..moves_index].join(" ")
+					} else {
+						STARTING_FEN.to_string()
+					};
 
-				// "moves" is for the opening book
-				moves.clear();
-				for coordinates in command_split.iter().skip(3) {
-					moves += &format!("{} ", coordinates);
+				if fen == root_fen {
+					// Reset the board to the initial position
+					for _ in 0..board.moves.len() {
+						board.undo_last_move();
+					}
+				} else {
+					board = Board::from_fen(&fen);
+					board.set_eval_params(bot.eval_params.clone());
+					board.tablebases = Some(Arc::clone(&bot.tablebases));
+					root_fen = fen;
+				}
 
+				for coordinates in command_split.iter().skip(moves_index + 1) {
 					if !move_str_is_valid(coordinates) {
 						println!("Illegal move: {}", coordinates);
 						break;
@@ -136,7 +256,6 @@ fn main() {
 						// log.write(err);
 					}
 				}
-				moves.pop();
 			}
 
 			"go" => {
@@ -178,14 +297,27 @@ fn main() {
 					}
 				}
 
-				bot.start(&mut board, moves.clone(), my_time, depth_to_search);
+				bot.start(&mut board, my_time, depth_to_search);
 
 				println!("bestmove {}", bot.best_move.to_coordinates());
 				// log.write(format!("bestmove {}", bot.best_move.to_coordinates()));
 			}
 
+			// Not part of UCI, GUIs never say how a game ended, so this has to come from whatever runs the games (before ucinewgame)
+			// Format: result <1-0 / 0-1 / 1/2-1/2>
+			"result" => {
+				if let Some(result) = command_split.get(1).and_then(|result| parse_result(result)) {
+					bot.book_learning.learn(result);
+				} else {
+					println!("Usage: result <1-0 / 0-1 / 1/2-1/2>");
+				}
+			}
+
 			"stop" => bot.search_cancelled = true, // Now that I think about it, this doesn't actually do anything LMAO
-			"quit" => break,
+			"quit" => {
+				bot.experience.save();
+				break;
+			}
 
 			// My debug tools
 
@@ -224,12 +356,31 @@ fn main() {
 			"bitboards" => board.print_bitboards(),
 			"castlingrights" => print_castling_rights(board.board_state.current.castling_rights),
 			"zobrist" => println!("{}", board.zobrist.key.current),
+			"polyglot" => println!("{:016x}", polyglot_key(&board)),
+			"eco" => {
+				if let Some(opening) = board.find_opening() {
+					println!("{} {}", opening.code, opening.name);
+				} else {
+					println!("Unknown opening");
+				}
+			}
+
+			// pgn (<result>): prints the game so far as PGN
+			"pgn" => print!("{}", write_game(&mut board, &root_fen, command_split.get(1).unwrap_or(&"*"))),
+
 			"fiftymoves" => println!("{}", board.board_state.current.fifty_move_counter),
 
 			"hceval" => println!("{}", board.hc_evaluate() * board.perspective()),
+			"eval" => board.trace_evaluation().print(),
 
-			// "rawnnueeval" => println!("{}", board.raw_nnue_evaluate()),
-			// "nnueeval" => println!("{}", board.nnue_evaluate() * board.perspective()),
+			"rawnnueeval" => {
+				board.set_nnue(true);
+				println!("{}", board.raw_nnue_evaluate());
+			}
+			"nnueeval" => {
+				board.set_nnue(true);
+				println!("{}", board.nnue_evaluate() * board.perspective());
+			}
 
 			"ttsize" => bot.transposition_table.print_size(),
 			"cleartt" => {
@@ -244,6 +395,74 @@ fn main() {
 				}
 			}
 
+			// gentb (<table name>): generates Maxwell's own tablebases into the tablebase_path folder,
+			// either every 3 and 4 piece table, or just one (and the smaller tables it needs)
+			"gentb" => {
+				let mut tablebases = EndgameTablebases::new(&bot_config.tablebase_path);
+				if tablebases.folder.as_os_str().is_empty() {
+					println!("No tablebase_path set");
+				} else {
+					if let Some(name) = command_split.get(1) {
+						tablebases.generate(name);
+					} else {
+						tablebases.generate_all();
+					}
+
+					bot.tablebases = Arc::new(EndgameTablebases::new(&bot_config.tablebase_path));
+					board.tablebases = Some(Arc::clone(&bot.tablebases));
+				}
+			}
+
+			// makebook <output file> <PGN files...> (max_ply=20) (min_games=3) (min_score=0)
+			"makebook" => {
+				if let Some(config) = BookMakerConfig::from_args(&command_split[1..]) {
+					make_book(&config);
+				} else {
+					println!("Usage: makebook <output file> <PGN files...> (max_ply=20) (min_games=3) (min_score=0)");
+				}
+			}
+
+			"tune" => {
+				if let Some(config) = TunerConfig::from_args(&command_split[1..]) {
+					tune(&config, &bot_config);
+				} else {
+					println!("Usage: tune <positions file> <output file> (epochs=100) (qsearch=false) (params=<file>) (threads=<all>)");
+				}
+			}
+
+			// saveparams <file>: writes the current evaluation parameters, to have a file to start editing from
+			"saveparams" => {
+				if let Some(path) = command_split.get(1) {
+					match bot.eval_params.save(path) {
+						Ok(()) => println!("Saved evaluation parameters to {}", path),
+						Err(error) => println!("Failed to save evaluation parameters to {}: {}", path, error),
+					}
+				}
+			}
+
+			// mergeexp <experience file>: merges another experience file into the current one
+			"mergeexp" => {
+				if let Some(path) = command_split.get(1) {
+					if !bot.experience.is_open() {
+						println!("No experience file set");
+					} else if bot.experience.merge_file(path) {
+						bot.experience.save();
+						bot.experience.preload(&mut bot.transposition_table);
+						println!("Experience has {} positions", bot.experience.entries.len());
+					} else {
+						println!("Failed to read {}", path);
+					}
+				}
+			}
+
+			"bench" => {
+				let depth = command_split.get(1)
+					.and_then(|depth| depth.parse::<u8>().ok())
+					.unwrap_or(BENCH_DEPTH);
+
+				bench(&bot_config, depth);
+			}
+
 			// _ => log.write(format!("Unknown command: {}", command)),
 			_ => {}
 		}
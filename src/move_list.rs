@@ -0,0 +1,41 @@
+use crate::move_data::{MoveData, NULL_MOVE};
+
+// No legal chess position has more than 218 moves, so this leaves plenty of room
+pub const MAX_MOVES: usize = 256;
+
+// A fixed size list that lives on the stack, so that generating moves never allocates
+#[derive(Copy, Clone)]
+pub struct MoveList {
+	moves: [MoveData; MAX_MOVES],
+	length: usize,
+}
+
+impl MoveList {
+	pub fn new() -> Self {
+		Self {
+			moves: [NULL_MOVE; MAX_MOVES],
+			length: 0,
+		}
+	}
+
+	pub fn len(&self) -> usize { self.length }
+	pub fn is_empty(&self) -> bool { self.length == 0 }
+	pub fn clear(&mut self) { self.length = 0; }
+
+	pub fn push(&mut self, data: MoveData) {
+		self.moves[self.length] = data;
+		self.length += 1;
+	}
+
+	pub fn get(&self, index: usize) -> MoveData { self.moves[index] }
+	pub fn swap(&mut self, a: usize, b: usize) { self.moves.swap(a, b); }
+
+	pub fn swap_remove(&mut self, index: usize) {
+		self.length -= 1;
+		self.moves[index] = self.moves[self.length];
+	}
+
+	pub fn iter(&self) -> std::slice::Iter<'_, MoveData> {
+		self.moves[..self.length].iter()
+	}
+}
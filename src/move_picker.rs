@@ -0,0 +1,248 @@
+/*
+The move picker hands out moves one at a time in stages, and only generates / sorts moves when it reaches
+the stage that needs them, so if the hash move causes a beta cutoff no moves get generated at all
+
+Hash move -> Winning captures -> Killer moves -> Counter move -> Quiets (sorted by history) -> Losing captures
+In quiescence search: Hash move -> Winning captures -> Quiet checks (optional) -> Losing captures
+
+Captures that lose material by static exchange evaluation are the losing ones
+*/
+
+use crate::scored_move_list::ScoredMoveList;
+use crate::killer_moves::KillerMoves;
+use crate::move_sorter::MoveSorter;
+use crate::move_data::{MoveData, NULL_MOVE};
+use crate::board::MoveGenType;
+use crate::pieces::*;
+use crate::Board;
+
+// Subtracted from the MVV-LVA score of losing captures, so they always score below the winning ones
+pub const LOSING_CAPTURE_OFFSET: i32 = 1000;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Stage {
+	HashMove,
+	GenerateCaptures,
+	WinningCaptures,
+	Killers,
+	CounterMove,
+	GenerateQuiets,
+	Quiets,
+	LosingCaptures,
+	Done,
+}
+
+pub struct MovePicker {
+	pub stage: Stage,
+	only_captures: bool,
+	quiet_checks: bool,
+
+	hash_move: MoveData,
+	killer_moves: KillerMoves,
+	killer_index: usize,
+	counter_move: MoveData,
+
+	// Captures are stored first, then the quiets get added after them
+	moves: ScoredMoveList,
+	captures_end: usize,
+	capture_index: usize,
+	quiet_index: usize,
+}
+
+impl MovePicker {
+	pub fn new(move_sorter: &MoveSorter, last_move: MoveData, hash_move: MoveData, ply: usize) -> Self {
+		Self {
+			stage: Stage::HashMove,
+			only_captures: false,
+			quiet_checks: false,
+
+			hash_move,
+			killer_moves: move_sorter.get_killer_moves(ply),
+			killer_index: 0,
+			counter_move: move_sorter.get_counter_move(last_move),
+
+			moves: ScoredMoveList::new(),
+			captures_end: 0,
+			capture_index: 0,
+			quiet_index: 0,
+		}
+	}
+
+	// Used in quiescence search: skips the killer and counter move stages,
+	// and the quiet stage only gets quiet checks if quiet_checks is true
+	pub fn captures(hash_move: MoveData, quiet_checks: bool) -> Self {
+		Self {
+			stage: Stage::HashMove,
+			only_captures: true,
+			quiet_checks,
+
+			hash_move,
+			killer_moves: KillerMoves::new(),
+			killer_index: 0,
+			counter_move: NULL_MOVE,
+
+			moves: ScoredMoveList::new(),
+			captures_end: 0,
+			capture_index: 0,
+			quiet_index: 0,
+		}
+	}
+
+	pub fn next(&mut self, board: &mut Board, move_sorter: &MoveSorter) -> Option<MoveData> {
+		loop {
+			match self.stage {
+				Stage::HashMove => {
+					self.stage = Stage::GenerateCaptures;
+
+					if self.hash_move != NULL_MOVE {
+						if let Some(m) = Self::find_on_board(board, self.hash_move) {
+							if !self.only_captures
+							|| m.capture != NO_PIECE as u8 {
+								self.hash_move = m;
+								return Some(m);
+							}
+						}
+
+						self.hash_move = NULL_MOVE;
+					}
+				}
+
+				Stage::GenerateCaptures => {
+					board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::Captures, self.moves.moves_mut());
+
+					let mut i = 0;
+					while i < self.moves.len() {
+						let m = self.moves.get(i);
+						if m == self.hash_move {
+							self.moves.remove(i);
+							continue;
+						}
+
+						let mut score = move_sorter.score_capture(m);
+						if board.static_exchange_evaluation(m) < 0 {
+							score -= LOSING_CAPTURE_OFFSET;
+						}
+
+						self.moves.set_score(i, score);
+						i += 1;
+					}
+
+					self.captures_end = self.moves.len();
+					self.stage = Stage::WinningCaptures;
+				}
+
+				Stage::WinningCaptures => {
+					if self.capture_index < self.captures_end {
+						let m = self.moves.pick(self.capture_index, self.captures_end);
+						if self.moves.score(self.capture_index) >= 0 {
+							self.capture_index += 1;
+							return Some(m);
+						}
+					}
+
+					self.stage =
+						if !self.only_captures {
+							Stage::Killers
+						} else if self.quiet_checks {
+							Stage::GenerateQuiets
+						} else {
+							Stage::LosingCaptures
+						};
+				}
+
+				Stage::Killers => {
+					if self.killer_index >= self.killer_moves.moves.len() {
+						self.stage = Stage::CounterMove;
+						continue;
+					}
+
+					let killer = self.killer_moves.moves[self.killer_index];
+					self.killer_index += 1;
+
+					if let Some(m) = self.quiet_on_board(board, killer) {
+						return Some(m);
+					}
+				}
+
+				Stage::CounterMove => {
+					self.stage = Stage::GenerateQuiets;
+
+					if !self.killer_moves.is_killer(self.counter_move) {
+						if let Some(m) = self.quiet_on_board(board, self.counter_move) {
+							return Some(m);
+						}
+					}
+
+					self.counter_move = NULL_MOVE;
+				}
+
+				Stage::GenerateQuiets => {
+					if self.only_captures {
+						board.get_quiet_checks_for_color(board.white_to_move, self.moves.moves_mut());
+					} else {
+						board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::Quiets, self.moves.moves_mut());
+					}
+
+					let mut i = self.captures_end;
+					while i < self.moves.len() {
+						let m = self.moves.get(i);
+						if m == self.hash_move
+						|| m == self.counter_move
+						|| self.killer_moves.is_killer(m) {
+							self.moves.remove(i);
+							continue;
+						}
+
+						self.moves.set_score(i, move_sorter.score_quiet(board.white_to_move, m));
+						i += 1;
+					}
+
+					self.quiet_index = self.captures_end;
+					self.stage = Stage::Quiets;
+				}
+
+				Stage::Quiets => {
+					if self.quiet_index < self.moves.len() {
+						let m = self.moves.pick(self.quiet_index, self.moves.len());
+						self.quiet_index += 1;
+						return Some(m);
+					}
+
+					self.stage = Stage::LosingCaptures;
+				}
+
+				Stage::LosingCaptures => {
+					if self.capture_index < self.captures_end {
+						let m = self.moves.pick(self.capture_index, self.captures_end);
+						self.capture_index += 1;
+						return Some(m);
+					}
+
+					self.stage = Stage::Done;
+				}
+
+				Stage::Done => return None,
+			}
+		}
+	}
+
+	// Killer and counter moves come from other positions, so they have to be a valid quiet move here,
+	// and not the hash move, which has already been searched
+	fn quiet_on_board(&self, board: &mut Board, data: MoveData) -> Option<MoveData> {
+		if data == NULL_MOVE
+		|| data == self.hash_move {
+			return None;
+		}
+
+		Self::find_on_board(board, data).filter(|m| m.capture == NO_PIECE as u8)
+	}
+
+	// Hash, killer and counter moves are only a from, to and flag, which might not even be possible in this position
+	fn find_on_board(board: &mut Board, data: MoveData) -> Option<MoveData> {
+		if board.is_pseudo_legal(data) {
+			Some(board.complete_move_data(data))
+		} else {
+			None
+		}
+	}
+}
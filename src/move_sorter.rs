@@ -17,6 +17,8 @@ pub const MVV_LVA: [i32; 36] = [
 pub struct MoveSorter {
 	pub killer_moves: [KillerMoves; MAX_SORT_MOVE_PLY],
 	pub history: [[[i32; 64]; 64]; 2],
+	// Indexed by the piece and end square of the previous move
+	pub counter_moves: [[MoveData; 64]; PIECE_COUNT],
 }
 
 impl MoveSorter {
@@ -24,12 +26,14 @@ impl MoveSorter {
 		Self {
 			killer_moves: [KillerMoves::new(); MAX_SORT_MOVE_PLY],
 			history: [[[0; 64]; 64]; 2],
+			counter_moves: [[NULL_MOVE; 64]; PIECE_COUNT],
 		}
 	}
 
 	pub fn clear(&mut self) {
 		self.killer_moves = [KillerMoves::new(); MAX_SORT_MOVE_PLY];
 		self.history = [[[0; 64]; 64]; 2];
+		self.counter_moves = [[NULL_MOVE; 64]; PIECE_COUNT];
 	}
 
 	pub fn add_killer_move(&mut self, data: MoveData, ply: usize) {
@@ -38,45 +42,33 @@ impl MoveSorter {
 		}
 	}
 
-	pub fn sort_moves(&mut self, white_to_move: bool, moves: Vec<MoveData>, hash_move: MoveData, ply: usize) -> Vec<(i32, MoveData)> {
-		if moves.is_empty() {
-			return vec![];
+	pub fn get_killer_moves(&self, ply: usize) -> KillerMoves {
+		if ply < MAX_SORT_MOVE_PLY {
+			self.killer_moves[ply]
+		} else {
+			KillerMoves::new()
 		}
+	}
 
-		let mut pairs = vec![];
-
-		for m in moves {
-			let mut score = 0;
-
-			if m == hash_move {
-				score = i32::MAX;
-			} else {
-				if m.capture == NO_PIECE as u8 {
-					if ply < MAX_SORT_MOVE_PLY
-					&& self.killer_moves[ply].is_killer(m) {
-						score += 5000;
-					}
-
-					score += self.history[white_to_move as usize][m.from as usize][m.to as usize];
-				} else {
-					score += 8000 + MVV_LVA[get_piece_type(m.piece as usize) * 6 + get_piece_type(m.capture as usize)];
+	pub fn add_counter_move(&mut self, data: MoveData, last_move: MoveData) {
+		if last_move != NULL_MOVE {
+			self.counter_moves[last_move.piece as usize][last_move.to as usize] = data;
+		}
+	}
 
-					// TODO: static exchange evaluation
-				}
+	pub fn get_counter_move(&self, last_move: MoveData) -> MoveData {
+		if last_move == NULL_MOVE {
+			return NULL_MOVE;
+		}
 
-				// TODO: re-test this
-				// if m.flag == SHORT_CASTLE_FLAG
-				// || m.flag == LONG_CASTLE_FLAG {
-				// 	score += 2000;
-				// } else if PROMOTABLE.contains(&m.flag) {
-				// 	score += BASE_WORTHS_OF_PIECE_TYPE[m.flag as usize] + 12000;
-				// }
-			}
+		self.counter_moves[last_move.piece as usize][last_move.to as usize]
+	}
 
-			pairs.push((score, m));
-		}
+	pub fn score_capture(&self, m: MoveData) -> i32 {
+		MVV_LVA[get_piece_type(m.piece as usize) * 6 + get_piece_type(m.capture as usize)]
+	}
 
-		pairs.sort_by(|a, b| b.0.cmp(&a.0));
-		pairs
+	pub fn score_quiet(&self, white_to_move: bool, m: MoveData) -> i32 {
+		self.history[white_to_move as usize][m.from as usize][m.to as usize]
 	}
-}
\ No newline at end of file
+}
@@ -8,6 +8,13 @@
 The input layer is effectively skipped, because the middle layer is incrementally updated,
 and the bucket is calculated based on how many pieces are left on the board
 
+The middle layer (the accumulator) is kept on a stack: every move pushes a copy of the last one and applies
+the pieces that moved to it, so undoing a move is just popping it back off
+
+Inference is quantized: the trained float weights get converted to integers at compile time, the accumulator is i16
+with the clipped ReLU going from 0 to QA instead of 0 to 1, and the output weights are i8 scaled by QB,
+so the output comes out scaled by QA * QB. float_evaluate is the float reference it gets checked against
+
 (Buckets are disabled for now)
 */
 
@@ -15,19 +22,61 @@ use crate::nnue_weights::*;
 use crate::move_data::{SHORT_CASTLE_FLAG, LONG_CASTLE_FLAG, EN_PASSANT_FLAG, MoveData};
 use crate::pieces::{WHITE_ROOK, BLACK_ROOK, NO_PIECE, PROMOTABLE, build_piece, is_piece_white, char_to_piece};
 use crate::Board;
-use rand::Rng;
 
 pub const NNUE_EVAL_SCALE: f32 = 100.0; // TODO: I have no idea what this should be
 pub const BUCKETS: usize = 8;
 
+pub const HIDDEN_LAYER_SIZE: usize = INPUT_LAYER_BIASES.len();
+
+// Every trained weight is within (-1, 1), so these keep the accumulator well inside an i16 and the output weights inside an i8
+pub const QA: i32 = 255;
+pub const QB: i32 = 128;
+
+pub static QUANTIZED_INPUT_WEIGHTS: [i16; INPUT_LAYER_WEIGHTS.len()] = quantize_i16(&INPUT_LAYER_WEIGHTS, QA);
+pub static QUANTIZED_INPUT_BIASES: [i16; HIDDEN_LAYER_SIZE] = quantize_i16(&INPUT_LAYER_BIASES, QA);
+pub static QUANTIZED_HIDDEN_WEIGHTS: [i8; HIDDEN_LAYER_SIZE] = quantize_i8(&HIDDEN_LAYER_WEIGHTS, QB);
+pub const QUANTIZED_HIDDEN_BIAS: i32 = round(HIDDEN_LAYER_BIASES[0] * (QA * QB) as f32);
+
+pub type Accumulator = [i16; HIDDEN_LAYER_SIZE];
+
+// The converters from the float weights, these panic at compile time if a weight doesn't fit
+const fn round(x: f32) -> i32 {
+	(if x < 0.0 { x - 0.5 } else { x + 0.5 }) as i32
+}
+
+const fn quantize_i16<const N: usize>(weights: &[f32; N], scale: i32) -> [i16; N] {
+	let mut quantized = [0; N];
+	let mut i = 0;
+	while i < N {
+		let value = round(weights[i] * scale as f32);
+		assert!(value >= i16::MIN as i32 && value <= i16::MAX as i32);
+		quantized[i] = value as i16;
+		i += 1;
+	}
+	quantized
+}
+
+const fn quantize_i8<const N: usize>(weights: &[f32; N], scale: i32) -> [i8; N] {
+	let mut quantized = [0; N];
+	let mut i = 0;
+	while i < N {
+		let value = round(weights[i] * scale as f32);
+		assert!(value >= i8::MIN as i32 && value <= i8::MAX as i32);
+		quantized[i] = value as i8;
+		i += 1;
+	}
+	quantized
+}
+
 pub struct NNUE {
-	pub input_layer: Vec<f32>,
+	// The last one is the current position's
+	pub accumulators: Vec<Accumulator>,
 }
 
 impl NNUE {
 	pub fn new() -> Self {
 		Self {
-			input_layer: INPUT_LAYER_BIASES.to_vec(),
+			accumulators: vec![QUANTIZED_INPUT_BIASES],
 		}
 	}
 
@@ -47,7 +96,7 @@ impl NNUE {
 	}
 
 	pub fn setup_fen(&mut self, fen: &String) {
-		self.input_layer = INPUT_LAYER_BIASES.to_vec();
+		self.accumulators = vec![QUANTIZED_INPUT_BIASES];
 
 		let fen_split = fen.split(' ').collect::<Vec<&str>>();
 
@@ -71,25 +120,29 @@ impl NNUE {
 		square as usize * 12 + piece as usize
 	}
 
+	pub fn current(&self) -> &Accumulator { self.accumulators.last().unwrap() }
+
 	pub fn activate(&mut self, square: u8, piece: u8) {
-		let length = self.input_layer.len();
-		let index = Self::get_index(square, piece);
+		let index = Self::get_index(square, piece) * HIDDEN_LAYER_SIZE;
+		let weights = &QUANTIZED_INPUT_WEIGHTS[index..index + HIDDEN_LAYER_SIZE];
 
-		for i in 0..length {
-			self.input_layer[i] += INPUT_LAYER_WEIGHTS[index * length + i];
+		for (node, weight) in self.accumulators.last_mut().unwrap().iter_mut().zip(weights) {
+			*node += weight;
 		}
 	}
 
 	pub fn deactivate(&mut self, square: u8, piece: u8) {
-		let length = self.input_layer.len();
-		let index = Self::get_index(square, piece);
+		let index = Self::get_index(square, piece) * HIDDEN_LAYER_SIZE;
+		let weights = &QUANTIZED_INPUT_WEIGHTS[index..index + HIDDEN_LAYER_SIZE];
 
-		for i in 0..length {
-			self.input_layer[i] -= INPUT_LAYER_WEIGHTS[index * length + i];
+		for (node, weight) in self.accumulators.last_mut().unwrap().iter_mut().zip(weights) {
+			*node -= weight;
 		}
 	}
 
 	pub fn make_move(&mut self, data: &MoveData) {
+		self.accumulators.push(*self.current());
+
 		self.deactivate(data.from, data.piece);
 
 		if PROMOTABLE.contains(&data.flag) {
@@ -133,68 +186,125 @@ impl NNUE {
 		}
 	}
 
-	pub fn undo_move(&mut self, data: &MoveData) {
-		self.activate(data.from, data.piece);
-
-		if PROMOTABLE.contains(&data.flag) {
-			let promotion = build_piece(is_piece_white(data.piece as usize), data.flag as usize);
-			self.deactivate(data.to, promotion as u8);
-		} else {
-			self.deactivate(data.to, data.piece);
-
-			if data.flag == SHORT_CASTLE_FLAG {
-				if is_piece_white(data.piece as usize) {
-					self.activate(63, WHITE_ROOK as u8);
-					self.deactivate(61, WHITE_ROOK as u8);
-				} else {
-					self.activate(7, BLACK_ROOK as u8);
-					self.deactivate(5, BLACK_ROOK as u8);
-				}
-			} else if data.flag == LONG_CASTLE_FLAG {
-				if is_piece_white(data.piece as usize) {
-					self.activate(56, WHITE_ROOK as u8);
-					self.deactivate(59, WHITE_ROOK as u8);
-				} else {
-					self.activate(0, BLACK_ROOK as u8);
-					self.deactivate(3, BLACK_ROOK as u8);
-				}
-			}
-		}
-
-		if data.capture != NO_PIECE as u8 {
-			if data.flag == EN_PASSANT_FLAG {
-				let en_passant_square =
-					if is_piece_white(data.piece as usize) {
-						data.to + 8
-					} else {
-						data.to - 8
-					};
+	pub fn undo_move(&mut self) {
+		self.accumulators.pop();
+	}
 
-				self.activate(en_passant_square, data.capture);
-			} else {
-				self.activate(data.to, data.capture);
-			}
-		}
+	pub fn evaluate(&self) -> f32 {
+		self.output() as f32 / (QA * QB) as f32
 	}
 
-	pub fn evaluate(&self, total_piece_count: usize) -> f32 {
+	// Scaled by QA * QB
+	pub fn output(&self) -> i32 {
 		// There are a maximum of 32 pieces on a Chess board,
 		// so our max index is: (32 - 1) / 4 = 7.75 which then gets rounded down because we're dividing integers, so 7 which is what we want
 		// And there are a minimum of 2 pieces on a Chess board (Both kings)
 		// so our min index is: (2 - 1) / 4 = 0.25 which gets rounded down to 0
 		// let bucket = (total_piece_count - 1) / 4;
-		let mut output = HIDDEN_LAYER_BIASES[0];
+		let mut output = QUANTIZED_HIDDEN_BIAS;
+
+		// let bucket_offset = bucket * HIDDEN_LAYER_SIZE;
+
+		for (node, weight) in self.current().iter().zip(QUANTIZED_HIDDEN_WEIGHTS.iter()) {
+			output += Self::clipped_relu(*node) * *weight as i32;
+		}
+
+		output
+	}
 
-		// let bucket_offset = bucket * self.input_layer.len();
+	fn clipped_relu(x: i16) -> i32 {
+		(x as i32).clamp(0, QA)
+	}
+
+	// The unquantized network, evaluated from scratch with the float weights
+	pub fn float_evaluate(pieces: &[(u8, u8)]) -> f32 {
+		let mut accumulator = INPUT_LAYER_BIASES;
+
+		for (square, piece) in pieces {
+			let index = Self::get_index(*square, *piece) * HIDDEN_LAYER_SIZE;
+			for (node, weight) in accumulator.iter_mut().zip(&INPUT_LAYER_WEIGHTS[index..index + HIDDEN_LAYER_SIZE]) {
+				*node += weight;
+			}
+		}
 
-		for i in 0..self.input_layer.len() {
-			output += Self::clipped_relu(self.input_layer[i]) * HIDDEN_LAYER_WEIGHTS[i];
+		let mut output = HIDDEN_LAYER_BIASES[0];
+		for (node, weight) in accumulator.iter().zip(HIDDEN_LAYER_WEIGHTS.iter()) {
+			output += node.clamp(0.0, 1.0) * weight;
 		}
 
 		output
 	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::pieces::{BLACK_KING, WHITE_KING};
+	use rand::{Rng, SeedableRng};
+	use rand_pcg::Pcg64;
+
+	// 10 centipawns with NNUE_EVAL_SCALE, the error is usually under 1
+	const TOLERANCE: f32 = 0.1;
+
+	fn fen_pieces(fen: &str) -> Vec<(u8, u8)> {
+		let mut pieces = vec![];
+		let mut i = 0;
+
+		for c in fen.split(' ').next().unwrap().chars() {
+			if let Some(empty_squares) = c.to_digit(10) {
+				i += empty_squares as u8;
+			} else if c != '/' {
+				pieces.push((i, char_to_piece(c) as u8));
+				i += 1;
+			}
+		}
+
+		pieces
+	}
+
+	fn assert_within_tolerance(nnue: &NNUE, pieces: &[(u8, u8)]) {
+		let quantized = nnue.evaluate();
+		let float = NNUE::float_evaluate(pieces);
+		assert!((quantized - float).abs() <= TOLERANCE, "quantized {} vs float {} for {:?}", quantized, float, pieces);
+	}
+
+	#[test]
+	fn quantized_matches_float_on_fens() {
+		for fen in [
+			crate::STARTING_FEN,
+			"r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+			"r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+			"8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+			"8/8/4k3/8/2K5/8/3Q4/8 w - - 0 1",
+		] {
+			let mut nnue = NNUE::new();
+			nnue.setup_fen(&fen.to_string());
+			assert_within_tolerance(&nnue, &fen_pieces(fen));
+		}
+	}
+
+	#[test]
+	fn quantized_matches_float_on_random_positions() {
+		let mut rng = Pcg64::seed_from_u64(50);
+
+		for _ in 0..10000 {
+			let mut squares = (0..64).collect::<Vec<u8>>();
+			let mut pieces = vec![];
+
+			let piece_count = rng.gen_range(0..30);
+			let other_pieces = (0..piece_count).map(|_| rng.gen_range(0..12)).collect::<Vec<usize>>();
+
+			for piece in [WHITE_KING, BLACK_KING].into_iter().chain(other_pieces) {
+				let square = squares.swap_remove(rng.gen_range(0..squares.len()));
+				pieces.push((square, piece as u8));
+			}
+
+			let mut nnue = NNUE::new();
+			for (square, piece) in &pieces {
+				nnue.activate(*square, *piece);
+			}
 
-	fn clipped_relu(x: f32) -> f32 {
-		x.clamp(0.0, 1.0)
+			assert_within_tolerance(&nnue, &pieces);
+		}
 	}
-}
\ No newline at end of file
+}
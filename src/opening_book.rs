@@ -1,15 +1,25 @@
-use rand::prelude::SliceRandom;
-use rand::thread_rng;
-use crate::move_data::NULL_MOVE;
-use crate::MoveData;
-
+use std::collections::HashMap;
+use crate::polyglot::polyglot_key;
+use crate::polyglot_book::book_move_to_move_data;
+use crate::utils::move_str_is_valid;
+use crate::{Board, MoveData, STARTING_FEN};
+
+// The lines are played through once when the book is created, and every move is stored under the position it's played from,
+// so lines that transpose into each other share their moves, and the book works no matter how a position was reached
+//
+// Books made by makebook can also be loaded from a text file, one move per line:
+//     <polyglot key in hex> <move coordinates> <weight>
 pub struct OpeningBook {
 	pub lines: Vec<&'static str>,
+	// Every move is stored with its weight, the built in lines all have a weight of 1
+	pub positions: HashMap<u64, Vec<(MoveData, u16)>>,
 }
 
 impl OpeningBook {
 	pub fn create() -> Self {
-		Self {
+		let mut book = Self {
+			positions: HashMap::new(),
+
 			lines: vec![
 				"e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 d2d3 f8c5 e1g1",
 				"e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 d2d3 f8c5 c1g5 h7h6",
@@ -46,37 +56,87 @@ impl OpeningBook {
 
 				"e2e4 Xg7g6 d2d4 f8g7 b1c3 d7d6 c1e3",
 			],
+		};
+
+		let mut board = Board::from_fen(STARTING_FEN);
+		for line in book.lines.iter() {
+			for coordinates in line.split(' ') {
+				// Moves marked with an X are still played, but never picked from the book
+				let excluded = coordinates.starts_with('X');
+
+				let Some(data) = book_move_to_move_data(&mut board, coordinates.trim_start_matches('X')) else {
+					println!("Illegal book move: {} in {}", coordinates, line);
+					break;
+				};
+
+				let key = polyglot_key(&board);
+				board.make_move(data);
+
+				if !excluded {
+					let moves = book.positions.entry(key).or_default();
+					if !moves.iter().any(|(book_move, _)| *book_move == data) {
+						moves.push((data, 1));
+					}
+				}
+			}
+
+			while board.undo_last_move() {}
 		}
-	}
 
-	pub fn get_opening_move(&mut self, moves: String) -> MoveData {
-		let moves_played = if moves.is_empty() { 0 } else { moves.matches(' ').count() + 1 };
-		let mut opening_moves = vec![];
+		book
+	}
 
-		for line in self.lines.iter() {
-			let line_without_xs = line.replace('X', "");
-			if line_without_xs.starts_with(&moves) {
-				let start = moves.len() + if moves_played == 0 { 0 } else { 1 };
+	// .bin files are Polyglot books, so those (and no book file at all) get the built in lines
+	pub fn open(path: &str) -> Self {
+		if path.is_empty()
+		|| path == "<empty>"
+		|| path.ends_with(".bin") {
+			return Self::create();
+		}
 
-				if start >= line.len() {
-					continue;
-				}
+		Self::load(path).unwrap_or_else(|| {
+			println!("info string Failed to open book {}", path);
+			Self::create()
+		})
+	}
 
-				let next_move = line.split(' ').collect::<Vec<&str>>()[moves_played];
+	pub fn load(path: &str) -> Option<Self> {
+		let text = std::fs::read_to_string(path).ok()?;
+		let mut positions: HashMap<u64, Vec<(MoveData, u16)>> = HashMap::new();
 
-				if next_move.contains('X') {
-					continue;
-				}
+		for line in text.lines() {
+			let line = line.trim();
+			if line.is_empty()
+			|| line.starts_with('#') {
+				continue;
+			}
 
-				let data = MoveData::from_coordinates(next_move.to_string());
-				opening_moves.push(data);
+			let split = line.split(' ').collect::<Vec<&str>>();
+			if split.len() != 3
+			|| !move_str_is_valid(split[1]) {
+				return None;
 			}
-		}
 
-		if let Some(m) = opening_moves.choose(&mut thread_rng()) {
-			return *m;
+			let key = u64::from_str_radix(split[0], 16).ok()?;
+			let weight = split[2].parse::<u16>().ok()?;
+			positions.entry(key).or_default().push((MoveData::from_coordinates(split[1].to_string()), weight));
 		}
 
-		NULL_MOVE
+		Some(Self {
+			lines: vec![],
+			positions,
+		})
 	}
-}
\ No newline at end of file
+
+	// Every legal book move in this position with its weight
+	pub fn get_moves(&self, board: &mut Board) -> Vec<(MoveData, u32)> {
+		let Some(book_moves) = self.positions.get(&polyglot_key(board)) else {
+			return vec![];
+		};
+
+		// Loaded books only know the coordinates, so they're matched against the legal moves (which also protects against key collisions)
+		book_moves.iter()
+			.filter_map(|(data, weight)| Some((book_move_to_move_data(board, &data.to_coordinates())?, *weight as u32)))
+			.collect()
+	}
+}
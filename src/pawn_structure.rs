@@ -0,0 +1,200 @@
+/*
+Pawn structure evaluation
+
+The pawn-only terms (everything that only depends on where the pawns are) change much less often than the rest of the board,
+so they're cached in a small hash table keyed by the pawn-only Zobrist key, and only get calculated again when a pawn moves.
+Passed pawns are saved along with the score, because how good they are also depends on the kings and the other pieces
+*/
+
+use crate::evaluation::{AttackInfo, file_bitboard, relative_rank};
+use crate::endgames::distance;
+use crate::score::{Score, s};
+use crate::utils::{pop_lsb, get_lsb};
+use crate::pieces::*;
+use crate::Board;
+
+pub const PAWN_HASH_ENTRIES: usize = 16384;
+
+pub const DOUBLED_PAWN_PENALTY: Score = s(11, 35);
+pub const ISOLATED_PAWN_PENALTY: Score = s(6, 20);
+// No friendly pawns beside or behind it to protect it, and an enemy pawn stops it from moving up to them
+pub const BACKWARD_PAWN_PENALTY: Score = s(9, 18);
+
+// All by relative rank, 0 = the color's back rank
+pub const SUPPORTED_PAWN_BONUS: [Score; 8] = [s(0, 0), s(0, 0), s(6, 4), s(8, 6), s(13, 11), s(25, 22), s(40, 40), s(0, 0)];
+pub const PHALANX_PAWN_BONUS: [Score; 8] = [s(0, 0), s(3, 2), s(5, 4), s(9, 7), s(18, 15), s(35, 32), s(55, 55), s(0, 0)];
+// Not passed yet, but nothing in front of it on its file, and at least as many friendly pawns beside it as enemy pawns in the way
+pub const CANDIDATE_PASSER_BONUS: [Score; 8] = [s(0, 0), s(2, 5), s(2, 5), s(4, 10), s(7, 18), s(12, 30), s(0, 0), s(0, 0)];
+pub const PASSED_PAWN_BOOST: [Score; 8] = [s(0, 0), s(5, 15), s(5, 15), s(9, 30), s(15, 50), s(27, 90), s(45, 150), s(0, 0)];
+
+// These aren't cached: they're multiplied by PASSED_RANK_WEIGHT and the king's distance to the square in front of the pawn
+pub const PASSED_RANK_WEIGHT: [i32; 8] = [0, 0, 0, 1, 3, 5, 8, 0];
+pub const PASSED_ENEMY_KING_DISTANCE: Score = s(0, 2);
+pub const PASSED_OWN_KING_DISTANCE: Score = s(0, 1);
+// Nothing on the way to the promotion square
+pub const PASSED_FREE_PATH_BONUS: [Score; 8] = [s(0, 0), s(0, 0), s(0, 0), s(2, 4), s(4, 10), s(8, 20), s(12, 32), s(0, 0)];
+// None of the squares on the way are attacked by the enemy
+pub const PASSED_SAFE_PATH_BONUS: [Score; 8] = [s(0, 0), s(0, 0), s(0, 0), s(3, 6), s(6, 14), s(12, 28), s(18, 45), s(0, 0)];
+// An enemy piece is sitting right in front of it
+pub const PASSED_BLOCKED_PENALTY: [Score; 8] = [s(0, 0), s(0, 0), s(1, 3), s(2, 5), s(4, 10), s(6, 16), s(10, 25), s(0, 0)];
+
+#[derive(Copy, Clone)]
+pub struct PawnEntry {
+	pub key: u64,
+	// Both indexed by color (white = 1)
+	pub score: [Score; 2],
+	pub passed: [u64; 2],
+}
+
+#[derive(Default)]
+pub struct PawnHashTable {
+	// Empty until the first evaluation, so boards that never get evaluated don't have to allocate it
+	pub table: Vec<Option<PawnEntry>>,
+}
+
+impl PawnHashTable {
+	pub fn get(&self, key: u64) -> Option<PawnEntry> {
+		if self.table.is_empty() {
+			return None;
+		}
+
+		self.table[key as usize % PAWN_HASH_ENTRIES].filter(|entry| entry.key == key)
+	}
+
+	pub fn store(&mut self, entry: PawnEntry) {
+		if self.table.is_empty() {
+			self.table = vec![None; PAWN_HASH_ENTRIES];
+		}
+
+		self.table[entry.key as usize % PAWN_HASH_ENTRIES] = Some(entry);
+	}
+}
+
+// Every square in front of the pawn on its file, up to the promotion square
+pub fn front_span(i: usize, white: bool) -> u64 {
+	if white {
+		file_bitboard(i % 8) & ((1 << i) - 1)
+	} else {
+		file_bitboard(i % 8) & (u64::MAX << i << 1)
+	}
+}
+
+impl Board {
+	pub fn pawn_structure(&mut self) -> PawnEntry {
+		let key = self.zobrist.pawn_key.current;
+		if let Some(entry) = self.pawn_hash.get(key) {
+			return entry;
+		}
+
+		let (white_score, white_passed) = self.evaluate_pawns(true);
+		let (black_score, black_passed) = self.evaluate_pawns(false);
+
+		let entry = PawnEntry {
+			key,
+			score: [black_score, white_score],
+			passed: [black_passed, white_passed],
+		};
+
+		self.pawn_hash.store(entry);
+		entry
+	}
+
+	// The pawn-only terms for one side, and a bitboard of its passed pawns
+	fn evaluate_pawns(&self, white: bool) -> (Score, u64) {
+		let params = &self.eval_params;
+		let mut score = Score::ZERO;
+		let mut passed = 0;
+
+		let color = white as usize;
+		let enemy = 1 - color;
+		let data = &self.precalculated_move_data;
+
+		let pawns = self.piece_bitboards[build_piece(white, PAWN)];
+		let enemy_pawns = self.piece_bitboards[build_piece(!white, PAWN)];
+		let enemy_pawn_attacks = self.pawn_attacks_bitboard(!white);
+
+		let mut bitboard = pawns;
+		while bitboard != 0 {
+			let i = pop_lsb(&mut bitboard) as usize;
+			let rank = relative_rank(i, white);
+			let stop_square = if white { i - 8 } else { i + 8 };
+
+			let neighbours = data.files_beside_square[i] & pawns;
+			let neighbours_behind = neighbours & !data.squares_ahead_of_pawn[color][i];
+			// A pawn on the square a pawn of the other color would attack from here protects it
+			let supporters = data.pawn_attacks[enemy][i] & pawns;
+			let phalanx = neighbours & 0xFF << (i / 8 * 8);
+
+			if data.file_of_square[i] & pawns != 0 {
+				score -= params.doubled_pawn_penalty;
+			}
+
+			if neighbours == 0 {
+				score -= params.isolated_pawn_penalty;
+			} else if neighbours_behind == 0
+			&& enemy_pawn_attacks & 1 << stop_square != 0 {
+				score -= params.backward_pawn_penalty;
+			}
+
+			if supporters != 0 {
+				score += params.supported_pawn_bonus[rank];
+			}
+
+			if phalanx != 0 {
+				score += params.phalanx_pawn_bonus[rank];
+			}
+
+			let sentries = data.squares_ahead_of_pawn[color][i] & enemy_pawns;
+			let front_file = data.file_in_front_of_pawn[color][i];
+
+			if sentries == 0
+			&& front_file & pawns == 0 {
+				passed |= 1 << i;
+				score += params.passed_pawn_boost[rank];
+			} else if front_file & (pawns | enemy_pawns) == 0
+			&& neighbours_behind.count_ones() >= sentries.count_ones() {
+				score += params.candidate_passer_bonus[rank];
+			}
+		}
+
+		(score, passed)
+	}
+
+	// The passed pawn terms that depend on more than just the pawns
+	pub fn evaluate_passed_pawns(&self, white: bool, passed: u64, info: &AttackInfo) -> Score {
+		let params = &self.eval_params;
+		let mut score = Score::ZERO;
+
+		let enemy = !white as usize;
+		let king_index = get_lsb(self.piece_bitboards[build_piece(white, KING)]) as usize;
+		let enemy_king_index = get_lsb(self.piece_bitboards[build_piece(!white, KING)]) as usize;
+		let occupied = self.color_bitboards[0] | self.color_bitboards[1];
+
+		let mut bitboard = passed;
+		while bitboard != 0 {
+			let i = pop_lsb(&mut bitboard) as usize;
+			let rank = relative_rank(i, white);
+			let stop_square = if white { i - 8 } else { i + 8 };
+
+			let weight = params.passed_rank_weight[rank];
+			score += params.passed_enemy_king_distance * (distance(enemy_king_index, stop_square) * weight);
+			score -= params.passed_own_king_distance * (distance(king_index, stop_square) * weight);
+
+			if self.color_bitboards[enemy] & 1 << stop_square != 0 {
+				score -= params.passed_blocked_penalty[rank];
+				continue;
+			}
+
+			let path = front_span(i, white);
+			if path & occupied == 0 {
+				score += params.passed_free_path_bonus[rank];
+			}
+
+			if path & info.attacked[enemy] == 0 {
+				score += params.passed_safe_path_bonus[rank];
+			}
+		}
+
+		score
+	}
+}
@@ -1,6 +1,8 @@
 use crate::NO_PIECE;
 use crate::move_data::*;
 use crate::Board;
+use crate::board::MoveGenType;
+use crate::move_list::MoveList;
 use std::time::Instant;
 use crate::pieces::PROMOTABLE;
 
@@ -43,7 +45,10 @@ fn perft(board: &mut Board, results: &mut PerftResults, depth: u8, ply: u8) {
 		return;
 	}
 
-	for data in board.get_pseudo_legal_moves_for_color(board.white_to_move, false) {
+	let mut moves = MoveList::new();
+	board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+
+	for data in moves.iter().copied() {
 		if !board.make_move(data) {
 			continue;
 		}
@@ -77,4 +82,164 @@ fn perft(board: &mut Board, results: &mut PerftResults, depth: u8, ply: u8) {
 			println!("{}: {}", data.to_coordinates(), positions_this_move);
 		}
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{STARTING_FEN, KIWIPETE_FEN, TEST_POSITION_4};
+	use crate::nnue::NNUE;
+
+	// The usual perft test positions (https://www.chessprogramming.org/Perft_Results)
+	const PERFT_FENS: [&str; 6] = [
+		STARTING_FEN,
+		KIWIPETE_FEN,
+		"8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+		TEST_POSITION_4,
+		"rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+		"r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+	];
+
+	// Every double pawn push can be taken en passant by one black pawn, and there's another one two files away that can't
+	const EN_PASSANT_FEN: &str = "4k3/8/8/8/p2pp3/8/1PP5/4K3 w - - 0 1";
+
+	// Calls check on every position up to the depth
+	fn walk(board: &mut Board, depth: u8, check: &mut impl FnMut(&mut Board)) {
+		check(board);
+		if depth == 0 {
+			return;
+		}
+
+		let mut moves = MoveList::new();
+		board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+
+		for data in moves.iter().copied() {
+			if board.make_move(data) {
+				walk(board, depth - 1, check);
+				board.undo_last_move();
+			}
+		}
+	}
+
+	#[test]
+	fn gives_check_matches_making_the_move() {
+		for fen in PERFT_FENS.into_iter().chain([EN_PASSANT_FEN]) {
+			let mut board = Board::from_fen(fen);
+
+			walk(&mut board, 2, &mut |board| {
+				let mut moves = MoveList::new();
+				board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+
+				for data in moves.iter().copied() {
+					let gives_check = board.gives_check(data);
+					if !board.make_move(data) {
+						continue;
+					}
+
+					let in_check = board.king_in_check(board.white_to_move);
+					board.undo_last_move();
+
+					assert_eq!(gives_check, in_check, "{} in {}", data.to_coordinates(), fen);
+				}
+			});
+		}
+	}
+
+	#[test]
+	fn nnue_accumulator_matches_a_fresh_one_after_make_and_undo() {
+		fn assert_fresh(board: &Board, fen: &str) {
+			assert_eq!(board.nnue.current(), NNUE::initialize(board).current(), "{}", fen);
+		}
+
+		for (fen, depth) in PERFT_FENS.into_iter().map(|fen| (fen, 2)).chain([(EN_PASSANT_FEN, 3)]) {
+			let mut board = Board::from_fen(fen);
+			board.set_nnue(true);
+
+			walk(&mut board, depth, &mut |board| {
+				assert_fresh(board, fen);
+
+				let mut moves = MoveList::new();
+				board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+
+				// Illegal moves get undone inside make_move
+				for data in moves.iter().copied() {
+					if board.make_move(data) {
+						assert_fresh(board, fen);
+						board.undo_last_move();
+					}
+					assert_fresh(board, fen);
+				}
+			});
+		}
+
+		// Moves made before the NNUE gets turned on can still be undone
+		let mut board = Board::from_fen(KIWIPETE_FEN);
+		for coordinates in ["e1g1", "b4c3", "d5e6", "e8c8"] {
+			assert!(board.play_move(MoveData::from_coordinates(coordinates.to_string())));
+		}
+
+		board.set_nnue(true);
+		assert_eq!(board.nnue.accumulators.len(), 5);
+		while board.undo_last_move() {
+			assert_fresh(&board, KIWIPETE_FEN);
+		}
+	}
+
+	#[test]
+	fn is_pseudo_legal_accepts_exactly_the_generated_moves() {
+		for fen in PERFT_FENS.into_iter().chain([EN_PASSANT_FEN]) {
+			let mut board = Board::from_fen(fen);
+
+			walk(&mut board, 1, &mut |board| {
+				let mut moves = MoveList::new();
+				board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+
+				// Every flag, start and end square a hash move could have
+				for binary in 0..(LONG_CASTLE_FLAG as u16 + 1) << 12 {
+					let data = MoveData::from_binary(binary);
+					let generated = moves.iter().any(|m| *m == data);
+
+					assert_eq!(board.is_pseudo_legal(data), generated, "{} (flag {}) in {}", data.to_coordinates(), data.flag, fen);
+				}
+			});
+		}
+	}
+
+	#[test]
+	fn static_exchange_evaluation_of_known_captures() {
+		let cases = [
+			// Undefended pawn
+			("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5", 100),
+			// Knight for a pawn (https://www.chessprogramming.org/SEE_-_The_Swap_Algorithm)
+			("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5", 100 - 320),
+			// The rook behind the first one makes the recapture bad
+			("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5", 100),
+			// The king can't take back while the rook behind the other one still defends the square
+			("3rk3/3r4/8/8/8/1N6/3p4/4K3 w - - 0 1", "b3d2", 100 - 320),
+			// Promoting and getting the queen taken by the king still wins a rook
+			("3rk3/4P3/8/8/8/8/8/K7 w - - 0 1", "e7d8q", 500 + 800 - 900),
+		];
+
+		let find_move = |board: &mut Board, coordinates: &str| {
+			let mut moves = MoveList::new();
+			board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+			moves.iter().copied().find(|m| m.to_coordinates() == coordinates).unwrap()
+		};
+
+		for (fen, coordinates, expected) in cases {
+			let mut board = Board::from_fen(fen);
+			let data = find_move(&mut board, coordinates);
+
+			assert_eq!(board.static_exchange_evaluation(data), expected, "{} in {}", coordinates, fen);
+		}
+
+		// En passant wins a pawn, but the c2 pawn takes back
+		let mut board = Board::from_fen(EN_PASSANT_FEN);
+		let double_pawn_push = find_move(&mut board, "b2b4");
+		board.make_move(double_pawn_push);
+
+		let data = find_move(&mut board, "a4b3");
+		assert_eq!(data.flag, EN_PASSANT_FLAG);
+		assert_eq!(board.static_exchange_evaluation(data), 100 - 100);
+	}
+}
@@ -0,0 +1,375 @@
+/*
+A small PGN reader and writer: splits a file into games, skipping comments, variations and NAGs,
+and turns SAN moves ("Nbxd7+", "O-O", "e8=Q") into MoveData by matching them against the legal moves, and back
+*/
+
+use crate::board::MoveGenType;
+use crate::move_list::MoveList;
+use crate::move_data::{MoveData, SHORT_CASTLE_FLAG, LONG_CASTLE_FLAG, EN_PASSANT_FLAG};
+use crate::utils::{coordinate_to_index, SQUARE_COORDINATES};
+use crate::pieces::*;
+use crate::{Board, STARTING_FEN};
+
+pub struct PgnGame {
+	pub moves: Vec<String>,
+	// From white's perspective: 1.0 = white won, 0.5 = draw, 0.0 = black won, None = unfinished / unknown
+	pub result: Option<f32>,
+	// Games from a custom starting position
+	pub fen: Option<String>,
+}
+
+impl PgnGame {
+	fn new() -> Self {
+		Self {
+			moves: vec![],
+			result: None,
+			fen: None,
+		}
+	}
+}
+
+pub fn parse_result(result: &str) -> Option<f32> {
+	match result {
+		"1-0" => Some(1.0),
+		"0-1" => Some(0.0),
+		"1/2-1/2" => Some(0.5),
+		_ => None,
+	}
+}
+
+fn is_termination_marker(token: &str) -> bool {
+	matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+pub fn read_games(pgn: &str) -> Vec<PgnGame> {
+	let mut games = vec![];
+	let mut game = PgnGame::new();
+	let mut has_moves = false;
+
+	let mut token = String::new();
+	let mut chars = pgn.chars().peekable();
+	let mut variation_depth = 0;
+
+	// Every token is handled when the whitespace (or special character) after it is reached
+	let finish_token = |token: &mut String, game: &mut PgnGame, games: &mut Vec<PgnGame>, has_moves: &mut bool| {
+		if token.is_empty() {
+			return;
+		}
+
+		if is_termination_marker(token) {
+			if game.result.is_none() {
+				game.result = parse_result(token);
+			}
+			games.push(std::mem::replace(game, PgnGame::new()));
+			*has_moves = false;
+		} else if !token.starts_with('$') {
+			// "12." "12..." "12.e4"
+			let san = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+			if !san.is_empty() {
+				game.moves.push(san.to_string());
+				*has_moves = true;
+			}
+		}
+
+		token.clear();
+	};
+
+	while let Some(c) = chars.next() {
+		match c {
+			'{' => {
+				finish_token(&mut token, &mut game, &mut games, &mut has_moves);
+				for c in chars.by_ref() {
+					if c == '}' {
+						break;
+					}
+				}
+			}
+
+			';' => {
+				finish_token(&mut token, &mut game, &mut games, &mut has_moves);
+				for c in chars.by_ref() {
+					if c == '\n' {
+						break;
+					}
+				}
+			}
+
+			'(' => {
+				finish_token(&mut token, &mut game, &mut games, &mut has_moves);
+				variation_depth += 1;
+			}
+
+			')' => {
+				token.clear();
+				variation_depth = i32::max(0, variation_depth - 1);
+			}
+
+			_ if variation_depth > 0 => {}
+
+			'[' => {
+				finish_token(&mut token, &mut game, &mut games, &mut has_moves);
+
+				// A new tag section without a termination marker before it
+				if has_moves {
+					games.push(std::mem::replace(&mut game, PgnGame::new()));
+					has_moves = false;
+				}
+
+				let mut tag = String::new();
+				for c in chars.by_ref() {
+					if c == ']' {
+						break;
+					}
+					tag.push(c);
+				}
+
+				if let Some((name, value)) = tag.split_once(' ') {
+					let value = value.trim().trim_matches('"');
+					match name {
+						"Result" => game.result = parse_result(value),
+						"FEN" => game.fen = Some(value.to_string()),
+						_ => {}
+					}
+				}
+			}
+
+			_ if c.is_whitespace() => finish_token(&mut token, &mut game, &mut games, &mut has_moves),
+
+			_ => token.push(c),
+		}
+	}
+
+	finish_token(&mut token, &mut game, &mut games, &mut has_moves);
+	if has_moves {
+		games.push(game);
+	}
+
+	games
+}
+
+// Finds the legal move that a SAN move describes, or None if it's illegal or can't be read
+pub fn san_to_move_data(board: &mut Board, san: &str) -> Option<MoveData> {
+	let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+	let mut moves = MoveList::new();
+	board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+
+	let castle_flag = match san {
+		"O-O" | "0-0" => Some(SHORT_CASTLE_FLAG),
+		"O-O-O" | "0-0-0" => Some(LONG_CASTLE_FLAG),
+		_ => None,
+	};
+
+	let mut piece_type = PAWN;
+	let mut promotion = 0;
+	let mut to = 0;
+	let mut from_file = None;
+	let mut from_rank = None;
+
+	if castle_flag.is_none() {
+		let mut chars = san.chars().filter(|c| *c != 'x' && *c != '=').collect::<Vec<char>>();
+
+		if let Some(first) = chars.first() {
+			if "NBRQK".contains(*first) {
+				piece_type = get_piece_type(char_to_piece(*first));
+				chars.remove(0);
+			}
+		}
+
+		if let Some(last) = chars.last() {
+			if "NBRQ".contains(*last) {
+				promotion = get_piece_type(char_to_piece(*last)) as u8;
+				chars.pop();
+			}
+		}
+
+		if chars.len() < 2 {
+			return None;
+		}
+
+		let to_coordinates = chars[chars.len() - 2..].iter().collect::<String>();
+		if !crate::utils::SQUARE_COORDINATES.contains(&to_coordinates.as_str()) {
+			return None;
+		}
+		to = coordinate_to_index(&to_coordinates);
+
+		// Whatever is left is disambiguation
+		for c in chars[..chars.len() - 2].iter() {
+			if c.is_ascii_lowercase() {
+				from_file = Some(*c as u8 - b'a');
+			} else if c.is_ascii_digit() {
+				from_rank = Some(*c as u8 - b'1');
+			}
+		}
+	}
+
+	for data in moves.iter().copied() {
+		let matches =
+			if let Some(flag) = castle_flag {
+				data.flag == flag
+			} else {
+				   get_piece_type(data.piece as usize) == piece_type
+				&& data.to == to
+				&& data.flag != SHORT_CASTLE_FLAG
+				&& data.flag != LONG_CASTLE_FLAG
+				&& (promotion == 0 || data.flag == promotion)
+				&& from_file.is_none_or(|file| data.from % 8 == file)
+				&& from_rank.is_none_or(|rank| 7 - data.from / 8 == rank)
+			};
+
+		if matches
+		&& board.make_move(data) {
+			board.undo_last_move();
+			return Some(data);
+		}
+	}
+
+	None
+}
+
+fn has_legal_moves(board: &mut Board) -> bool {
+	let mut moves = MoveList::new();
+	board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+
+	for data in moves.iter().copied() {
+		if board.make_move(data) {
+			board.undo_last_move();
+			return true;
+		}
+	}
+
+	false
+}
+
+// The opposite of san_to_move_data, the move has to be legal
+pub fn move_to_san(board: &mut Board, data: MoveData) -> String {
+	let mut san = match data.flag {
+		SHORT_CASTLE_FLAG => "O-O".to_string(),
+		LONG_CASTLE_FLAG => "O-O-O".to_string(),
+
+		_ => {
+			let piece_type = get_piece_type(data.piece as usize);
+			let is_capture = data.capture != NO_PIECE as u8 || data.flag == EN_PASSANT_FLAG;
+			let to = SQUARE_COORDINATES[data.to as usize];
+			let from = SQUARE_COORDINATES[data.from as usize];
+
+			let mut san = String::new();
+			if piece_type == PAWN {
+				if is_capture {
+					san.push_str(&from[0..1]);
+				}
+			} else {
+				san.push(piece_to_char(build_piece(true, piece_type)));
+
+				// Other pieces of the same type that can go to the same square
+				let mut moves = MoveList::new();
+				board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+
+				let others = moves.iter()
+					.copied()
+					.filter(|other| other.to == data.to && other.from != data.from && get_piece_type(other.piece as usize) == piece_type)
+					.filter(|other| {
+						let legal = board.make_move(*other);
+						if legal {
+							board.undo_last_move();
+						}
+						legal
+					})
+					.collect::<Vec<MoveData>>();
+
+				if !others.is_empty() {
+					if others.iter().all(|other| other.from % 8 != data.from % 8) {
+						san.push_str(&from[0..1]);
+					} else if others.iter().all(|other| other.from / 8 != data.from / 8) {
+						san.push_str(&from[1..2]);
+					} else {
+						san.push_str(from);
+					}
+				}
+			}
+
+			if is_capture {
+				san.push('x');
+			}
+			san.push_str(to);
+
+			if PROMOTABLE.contains(&data.flag) {
+				san.push('=');
+				san.push(piece_to_char(build_piece(true, data.flag as usize)));
+			}
+
+			san
+		}
+	};
+
+	if board.make_move(data) {
+		if board.king_in_check(board.white_to_move) {
+			san.push(if has_legal_moves(board) { '+' } else { '#' });
+		}
+		board.undo_last_move();
+	}
+
+	san
+}
+
+// Writes the board's game as PGN, with the ECO code and opening name if it's a known opening
+pub fn write_game(board: &mut Board, root_fen: &str, result: &str) -> String {
+	let mut pgn = String::new();
+
+	for (name, value) in [("Event", "?"), ("Site", "?"), ("Date", "????.??.??"), ("Round", "?"), ("White", "?"), ("Black", "?"), ("Result", result)] {
+		pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+	}
+
+	if root_fen != STARTING_FEN {
+		pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", root_fen));
+	}
+
+	if let Some(opening) = board.find_opening() {
+		pgn.push_str(&format!("[ECO \"{}\"]\n[Opening \"{}\"]\n", opening.code, opening.name));
+	}
+
+	pgn.push('\n');
+
+	// Replay the game from the start to write every move in SAN
+	let moves = board.moves.clone();
+	while board.undo_last_move() {}
+
+	let mut move_number = root_fen.split(' ').nth(5).and_then(|number| number.parse::<usize>().ok()).unwrap_or(1);
+	let mut tokens = vec![];
+
+	for (i, data) in moves.iter().enumerate() {
+		if board.white_to_move {
+			tokens.push(format!("{}.", move_number));
+		} else if i == 0 {
+			tokens.push(format!("{}...", move_number));
+		}
+
+		tokens.push(move_to_san(board, *data));
+
+		if !board.white_to_move {
+			move_number += 1;
+		}
+		board.make_move(*data);
+	}
+
+	tokens.push(result.to_string());
+
+	// Lines are kept under 80 characters
+	let mut line_length = 0;
+	for token in tokens {
+		if line_length > 0
+		&& line_length + token.len() + 1 > 80 {
+			pgn.push('\n');
+			line_length = 0;
+		} else if line_length > 0 {
+			pgn.push(' ');
+			line_length += 1;
+		}
+
+		line_length += token.len();
+		pgn.push_str(&token);
+	}
+
+	pgn.push('\n');
+	pgn
+}
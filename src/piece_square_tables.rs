@@ -1,4 +1,5 @@
 use crate::pieces::*;
+use crate::score::{Score, s};
 
 // TODO: maybe combine base piece values and piece square tables?
 
@@ -35,7 +36,7 @@ pub const ENDGAME_PAWN_TABLE: [i32; 64] = [
 	  0,  0,  0,  0,  0,  0,  0,  0,
 ];
 
-pub const KNIGHT_TABLE: [i32; 64] = [
+pub const MIDDLEGAME_KNIGHT_TABLE: [i32; 64] = [
 	-50,-40,-30,-30,-30,-30,-40,-50,
 	-40,-20,  0,  0,  0,  0,-20,-40,
 	-30,  0, 10, 15, 15, 10,  0,-30,
@@ -46,7 +47,18 @@ pub const KNIGHT_TABLE: [i32; 64] = [
 	-50,-40,-30,-30,-30,-30,-40,-50,
 ];
 
-pub const BISHOP_TABLE: [i32; 64] = [
+pub const ENDGAME_KNIGHT_TABLE: [i32; 64] = [
+	-50,-40,-30,-30,-30,-30,-40,-50,
+	-40,-20,  0,  0,  0,  0,-20,-40,
+	-30,  0, 10, 15, 15, 10,  0,-30,
+	-30,  5, 15, 20, 20, 15,  5,-30,
+	-30,  0, 15, 20, 20, 15,  0,-30,
+	-30,  5, 10, 15, 15, 10,  5,-30,
+	-40,-20,  0,  5,  5,  0,-20,-40,
+	-50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+pub const MIDDLEGAME_BISHOP_TABLE: [i32; 64] = [
 	-20,-10,-10,-10,-10,-10,-10,-20,
 	-10,  0,  0,  0,  0,  0,  0,-10,
 	-10,  0,  5, 10, 10,  5,  0,-10,
@@ -57,7 +69,18 @@ pub const BISHOP_TABLE: [i32; 64] = [
 	-20,-10,-10,-10,-10,-10,-10,-20,
 ];
 
-pub const ROOK_TABLE: [i32; 64] = [
+pub const ENDGAME_BISHOP_TABLE: [i32; 64] = [
+	-20,-10,-10,-10,-10,-10,-10,-20,
+	-10,  0,  0,  0,  0,  0,  0,-10,
+	-10,  0,  5, 10, 10,  5,  0,-10,
+	-10,  5,  5, 10, 10,  5,  5,-10,
+	-10,  0, 10, 10, 10, 10,  0,-10,
+	-10, 10, 10, 10, 10, 10, 10,-10,
+	-10,  5,  0,  0,  0,  0,  5,-10,
+	-20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+pub const MIDDLEGAME_ROOK_TABLE: [i32; 64] = [
 	  0,  0,  0,  0,  0,  0,  0,  0,
 	  5, 10, 10, 10, 10, 10, 10,  5,
 	 -5,  0,  0,  0,  0,  0,  0, -5,
@@ -68,7 +91,29 @@ pub const ROOK_TABLE: [i32; 64] = [
 	  0,  0,  0,  5,  5,  0,  0,  0,
 ];
 
-pub const QUEEN_TABLE: [i32; 64] = [
+pub const ENDGAME_ROOK_TABLE: [i32; 64] = [
+	  0,  0,  0,  0,  0,  0,  0,  0,
+	  5, 10, 10, 10, 10, 10, 10,  5,
+	 -5,  0,  0,  0,  0,  0,  0, -5,
+	 -5,  0,  0,  0,  0,  0,  0, -5,
+	 -5,  0,  0,  0,  0,  0,  0, -5,
+	 -5,  0,  0,  0,  0,  0,  0, -5,
+	 -5,  0,  0,  0,  0,  0,  0, -5,
+	  0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+pub const MIDDLEGAME_QUEEN_TABLE: [i32; 64] = [
+	-20,-10,-10,  0,  0,-10,-10,-20,
+	-10,  0,  0,  0,  0,  0,  0,-10,
+	-10,  0,  5,  5,  5,  5,  0,-10,
+	 -5,  0,  5,  5,  5,  5,  0, -5,
+	 -5,  0,  5,  5,  5,  5,  0, -5,
+	-10,  5,  5,  5,  5,  5,  0,-10,
+	-10,  0,  5,  0,  0,  0,  0,-10,
+	-20,-10,-10,  0,  0,-10,-10,-20,
+];
+
+pub const ENDGAME_QUEEN_TABLE: [i32; 64] = [
 	-20,-10,-10,  0,  0,-10,-10,-20,
 	-10,  0,  0,  0,  0,  0,  0,-10,
 	-10,  0,  5,  5,  5,  5,  0,-10,
@@ -119,20 +164,32 @@ pub const BASE_WORTHS_OF_PIECE_TYPE: [i32; 6] = [
 	KING_WORTH,
 ];
 
-pub fn get_full_worth_of_piece(piece: usize, mut i: usize, endgame: f32) -> i32 {
-	if !is_piece_white(piece) {
-		i = flip_index(i);
-	}
-
-	match get_piece_type(piece) {
-		PAWN   => PAWN_WORTH   + (MIDDLEGAME_PAWN_TABLE[i] as f32 * (1.0 - endgame) + ENDGAME_PAWN_TABLE[i] as f32 * endgame) as i32,
-		KNIGHT => KNIGHT_WORTH + KNIGHT_TABLE[i],
-		BISHOP => BISHOP_WORTH + BISHOP_TABLE[i],
-		ROOK   => ROOK_WORTH   + ROOK_TABLE[i],
-		QUEEN  => QUEEN_WORTH  + QUEEN_TABLE[i],
-
-		KING   => (MIDDLEGAME_KING_TABLE[i] as f32 * (1.0 - endgame) + ENDGAME_KING_TABLE[i] as f32 * endgame) as i32,
+// Middlegame and endgame values of each piece, the plain worths above are still used for things like move ordering and material counting
+pub const PIECE_VALUES: [Score; 6] = [
+	s(PAWN_WORTH,   PAWN_WORTH),
+	s(KNIGHT_WORTH, KNIGHT_WORTH),
+	s(BISHOP_WORTH, BISHOP_WORTH),
+	s(ROOK_WORTH,   ROOK_WORTH),
+	s(QUEEN_WORTH,  QUEEN_WORTH),
+	s(KING_WORTH,   KING_WORTH),
+];
 
-		_ => 0,
+const fn pack_tables(middlegame: &[i32; 64], endgame: &[i32; 64]) -> [Score; 64] {
+	let mut table = [Score::ZERO; 64];
+	let mut i = 0;
+	while i < 64 {
+		table[i] = s(middlegame[i], endgame[i]);
+		i += 1;
 	}
-}
\ No newline at end of file
+	table
+}
+
+// From white's perspective, indexed by piece type
+pub const PIECE_SQUARE_TABLES: [[Score; 64]; 6] = [
+	pack_tables(&MIDDLEGAME_PAWN_TABLE,   &ENDGAME_PAWN_TABLE),
+	pack_tables(&MIDDLEGAME_KNIGHT_TABLE, &ENDGAME_KNIGHT_TABLE),
+	pack_tables(&MIDDLEGAME_BISHOP_TABLE, &ENDGAME_BISHOP_TABLE),
+	pack_tables(&MIDDLEGAME_ROOK_TABLE,   &ENDGAME_ROOK_TABLE),
+	pack_tables(&MIDDLEGAME_QUEEN_TABLE,  &ENDGAME_QUEEN_TABLE),
+	pack_tables(&MIDDLEGAME_KING_TABLE,   &ENDGAME_KING_TABLE),
+];
@@ -88,4 +88,56 @@ pub fn piece_to_char(piece: usize) -> char {
 
 		_ => ' ',
 	}
-}
\ No newline at end of file
+}
+
+// Material signatures pack the count of every piece into a nibble, first side first:
+// [[pawns, knights, bishops, rooks, queens, kings] for the first side, [...] for the second side]
+pub fn material_key(counts: &[[u8; 6]; 2]) -> u64 {
+	let mut key = 0;
+	for (side, side_counts) in counts.iter().enumerate() {
+		for (piece_type, count) in side_counts.iter().enumerate() {
+			key |= (*count as u64) << (4 * (side * 6 + piece_type));
+		}
+	}
+	key
+}
+
+// "KRPvKR" -> counts, with the pieces before the "v" as the first side
+pub fn parse_material(name: &str) -> Option<[[u8; 6]; 2]> {
+	let (first, second) = name.split_once('v')?;
+	let mut counts = [[0; 6]; 2];
+
+	for (side, pieces) in [first, second].iter().enumerate() {
+		for c in pieces.chars() {
+			let piece = char_to_piece(c);
+			if piece == NO_PIECE
+			|| !is_piece_white(piece) {
+				return None;
+			}
+			counts[side][get_piece_type(piece)] += 1;
+		}
+
+		if counts[side][KING] != 1 {
+			return None;
+		}
+	}
+
+	Some(counts)
+}
+
+// The opposite of parse_material, with the pieces in KQRBNP order
+pub fn material_name(counts: &[[u8; 6]; 2]) -> String {
+	let mut name = String::new();
+	for (side, side_counts) in counts.iter().enumerate() {
+		if side == 1 {
+			name.push('v');
+		}
+
+		for piece_type in [KING, QUEEN, ROOK, BISHOP, KNIGHT, PAWN] {
+			for _ in 0..side_counts[piece_type] {
+				name.push(piece_to_char(build_piece(true, piece_type)));
+			}
+		}
+	}
+	name
+}
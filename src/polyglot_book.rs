@@ -0,0 +1,234 @@
+/*
+Reads and writes Polyglot .bin opening books
+
+A book is a list of 16 byte entries sorted by key, all big endian:
+	key (u64), move (u16), weight (u16), learn (u32)
+
+The move is packed as: promotion piece (3 bits, 1 = knight ... 4 = queen), from rank, from file, to rank, to file (3 bits each),
+and castling is written as the king capturing its own rook (e1h1 instead of e1g1)
+
+The file is binary searched on disk, so big books don't have to be loaded into memory
+*/
+
+use std::fs::File;
+use std::io::{Read, Write, Seek, SeekFrom};
+use rand::{thread_rng, Rng};
+use crate::board::MoveGenType;
+use crate::move_list::MoveList;
+use crate::move_data::{MoveData, NULL_MOVE, SHORT_CASTLE_FLAG, LONG_CASTLE_FLAG};
+use crate::utils::coordinate_to_index;
+use crate::polyglot::polyglot_key;
+use crate::pieces::*;
+use crate::Board;
+
+pub const ENTRY_SIZE: u64 = 16;
+
+#[derive(Copy, Clone, Debug)]
+pub struct BookEntry {
+	pub key: u64,
+	pub raw_move: u16,
+	pub weight: u16,
+	pub learn: u32,
+}
+
+impl BookEntry {
+	pub fn from_bytes(bytes: &[u8; ENTRY_SIZE as usize]) -> Self {
+		Self {
+			key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+			raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+			weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+			learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+		}
+	}
+
+	pub fn to_bytes(self) -> [u8; ENTRY_SIZE as usize] {
+		let mut bytes = [0; ENTRY_SIZE as usize];
+		bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+		bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+		bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+		bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+		bytes
+	}
+
+	// Coordinates like "e2e4" or "a7a8q", with castling still written as king takes rook
+	pub fn move_coordinates(&self) -> String {
+		let square = |bits: u16| format!("{}{}", (b'a' + (bits & 7) as u8) as char, (bits >> 3 & 7) + 1);
+
+		let mut coordinates = format!("{}{}", square(self.raw_move >> 6), square(self.raw_move));
+		let promotion = (self.raw_move >> 12 & 7) as usize;
+		if promotion != 0 {
+			coordinates.push(piece_to_char(promotion));
+		}
+
+		coordinates
+	}
+}
+
+pub struct PolyglotBook {
+	file: Option<File>,
+	entry_count: u64,
+}
+
+impl PolyglotBook {
+	pub fn empty() -> Self {
+		Self {
+			file: None,
+			entry_count: 0,
+		}
+	}
+
+	pub fn open(path: &str) -> Self {
+		// Anything else is a native book (see OpeningBook)
+		if !path.ends_with(".bin") {
+			return Self::empty();
+		}
+
+		match File::open(path) {
+			Ok(file) => {
+				let entry_count = file.metadata().map(|metadata| metadata.len() / ENTRY_SIZE).unwrap_or(0);
+				Self {
+					file: Some(file),
+					entry_count,
+				}
+			}
+
+			Err(_) => {
+				println!("info string Failed to open book {}", path);
+				Self::empty()
+			}
+		}
+	}
+
+	pub fn is_open(&self) -> bool { self.file.is_some() }
+
+	fn read_entry(&mut self, i: u64) -> Option<BookEntry> {
+		let file = self.file.as_mut()?;
+		let mut bytes = [0; ENTRY_SIZE as usize];
+
+		file.seek(SeekFrom::Start(i * ENTRY_SIZE)).ok()?;
+		file.read_exact(&mut bytes).ok()?;
+
+		Some(BookEntry::from_bytes(&bytes))
+	}
+
+	// Every entry for this key, which are all next to each other because the book is sorted
+	pub fn get_entries(&mut self, key: u64) -> Vec<BookEntry> {
+		let mut entries = vec![];
+
+		// Binary search for the first entry with this key
+		let mut low = 0;
+		let mut high = self.entry_count;
+		while low < high {
+			let middle = (low + high) / 2;
+			match self.read_entry(middle) {
+				Some(entry) if entry.key < key => low = middle + 1,
+				Some(_) => high = middle,
+				None => return entries,
+			}
+		}
+
+		for i in low..self.entry_count {
+			match self.read_entry(i) {
+				Some(entry) if entry.key == key => entries.push(entry),
+				_ => break,
+			}
+		}
+
+		entries
+	}
+
+	// Every legal book move in this position with its weight
+	pub fn get_moves(&mut self, board: &mut Board) -> Vec<(MoveData, u32)> {
+		self.get_entries(polyglot_key(board))
+			.into_iter()
+			.filter_map(|entry| Some((book_move_to_move_data(board, &entry.move_coordinates())?, entry.weight as u32)))
+			.collect()
+	}
+}
+
+// Picks a move randomly (weighted by how good the book thinks each move is),
+// or always the highest weighted move if best_move is true. Returns NULL_MOVE if there aren't any moves
+pub fn choose_book_move(moves: &[(MoveData, u32)], best_move: bool) -> MoveData {
+	if best_move {
+		return moves.iter()
+			.max_by_key(|(_, weight)| *weight)
+			.map(|(data, _)| *data)
+			.unwrap_or(NULL_MOVE);
+	}
+
+	let total_weight = moves.iter().map(|(_, weight)| weight).sum::<u32>();
+	if total_weight == 0 {
+		return moves.first().map(|(data, _)| *data).unwrap_or(NULL_MOVE);
+	}
+
+	let mut choice = thread_rng().gen_range(0..total_weight);
+	for (data, weight) in moves.iter() {
+		if choice < *weight {
+			return *data;
+		}
+		choice -= weight;
+	}
+
+	NULL_MOVE
+}
+
+// The opposite of BookEntry::move_coordinates
+pub fn encode_move(data: MoveData) -> u16 {
+	let to = match data.flag {
+		SHORT_CASTLE_FLAG => data.to + 1,
+		LONG_CASTLE_FLAG => data.to - 2,
+		_ => data.to,
+	};
+
+	let promotion = if PROMOTABLE.contains(&data.flag) { data.flag as u16 } else { 0 };
+
+	// Polyglot's squares start at a1
+	promotion << 12 | ((data.from ^ 56) as u16) << 6 | (to ^ 56) as u16
+}
+
+// Sorts the entries by key (and the best moves first), which is how Polyglot books have to be stored
+pub fn write_book(path: &str, entries: &mut [BookEntry]) -> std::io::Result<()> {
+	entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)));
+
+	let mut file = std::io::BufWriter::new(File::create(path)?);
+	for entry in entries.iter() {
+		file.write_all(&entry.to_bytes())?;
+	}
+
+	file.flush()
+}
+
+// Finds the legal move that the book's coordinates describe, so broken books or hash collisions can't play illegal moves
+pub fn book_move_to_move_data(board: &mut Board, coordinates: &str) -> Option<MoveData> {
+	let from = coordinate_to_index(&coordinates[0..2]);
+	let mut to = coordinate_to_index(&coordinates[2..4]);
+	let promotion = coordinates.chars().nth(4).map(|c| get_piece_type(char_to_piece(c)) as u8).unwrap_or(0);
+
+	// King takes rook -> the normal castling move
+	let piece = board.get_piece(from);
+	if piece != NO_PIECE
+	&& get_piece_type(piece) == KING
+	&& from / 8 == to / 8
+	&& (from % 8).abs_diff(to % 8) > 1 {
+		to = if to > from { from + 2 } else { from - 2 };
+	}
+
+	if piece == NO_PIECE
+	|| is_piece_white(piece) != board.white_to_move {
+		return None;
+	}
+
+	let mut moves = MoveList::new();
+	board.get_moves_for_piece(from, MoveGenType::All, &mut moves);
+
+	for data in moves.iter().copied() {
+		if data.to == to
+		&& (promotion == 0 || data.flag == promotion)
+		&& board.make_move(data) {
+			board.undo_last_move();
+			return Some(data);
+		}
+	}
+
+	None
+}
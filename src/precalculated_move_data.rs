@@ -130,7 +130,10 @@ impl PrecalculatedMoveData {
 
 			let mut files_beside_square = 0;
 
-			files_beside_square |= (A_FILE >> (8 - (i % 8) - 2)) & NOT_H_FILE;
+			// There's no file on the right of the h file
+			if i % 8 != 7 {
+				files_beside_square |= (A_FILE >> (8 - (i % 8) - 2)) & NOT_H_FILE;
+			}
 			files_beside_square |= (A_FILE >> (8 - (i % 8))) & NOT_A_FILE;
 
 			data.files_beside_square[i] = files_beside_square;
@@ -289,11 +292,11 @@ impl PrecalculatedMoveData {
 	}
 
 	pub fn generate_bishop_key(&self, piece_index: usize, occupancies: u64) -> usize {
-		((occupancies * self.magic_numbers.bishop[piece_index]) >> self.magic_numbers.bishop_shift[piece_index]) as usize
+		(occupancies.wrapping_mul(self.magic_numbers.bishop[piece_index]) >> self.magic_numbers.bishop_shift[piece_index]) as usize
 	}
 
 	pub fn generate_rook_key(&self, piece_index: usize, occupancies: u64) -> usize {
-		((occupancies * self.magic_numbers.rook[piece_index]) >> self.magic_numbers.rook_shift[piece_index]) as usize
+		(occupancies.wrapping_mul(self.magic_numbers.rook[piece_index]) >> self.magic_numbers.rook_shift[piece_index]) as usize
 	}
 
 	fn generate_sliding_moves_bitboard(
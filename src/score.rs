@@ -0,0 +1,64 @@
+/*
+A middlegame and an endgame score packed into one i32, so every evaluation term can be added up once,
+and the two halves only get split and blended by the game phase at the very end
+
+The endgame score is in the upper 16 bits, and the middlegame score in the lower 16 bits (which borrows from the upper half when it's negative,
+so unpacking the endgame score has to round it back)
+*/
+
+use std::ops::{Add, AddAssign, Sub, SubAssign, Neg, Mul};
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct Score(pub i32);
+
+pub const fn s(mg: i32, eg: i32) -> Score {
+	Score(((eg as u32) << 16) as i32 + mg)
+}
+
+// 0 = endgame, PHASE_MAX = middlegame
+pub const PHASE_MAX: i32 = 256;
+
+impl Score {
+	pub const ZERO: Score = Score(0);
+
+	pub const fn mg(&self) -> i32 { self.0 as u16 as i16 as i32 }
+	pub const fn eg(&self) -> i32 { ((self.0 as u32).wrapping_add(0x8000) >> 16) as u16 as i16 as i32 }
+
+	pub fn taper(&self, phase: i32) -> i32 {
+		(self.mg() * phase + self.eg() * (PHASE_MAX - phase)) / PHASE_MAX
+	}
+}
+
+impl Add for Score {
+	type Output = Score;
+	fn add(self, other: Score) -> Score { Score(self.0.wrapping_add(other.0)) }
+}
+
+impl AddAssign for Score {
+	fn add_assign(&mut self, other: Score) { *self = *self + other; }
+}
+
+impl Sub for Score {
+	type Output = Score;
+	fn sub(self, other: Score) -> Score { Score(self.0.wrapping_sub(other.0)) }
+}
+
+impl SubAssign for Score {
+	fn sub_assign(&mut self, other: Score) { *self = *self - other; }
+}
+
+impl Neg for Score {
+	type Output = Score;
+	fn neg(self) -> Score { Score(self.0.wrapping_neg()) }
+}
+
+impl Mul<i32> for Score {
+	type Output = Score;
+	fn mul(self, amount: i32) -> Score { Score(self.0.wrapping_mul(amount)) }
+}
+
+impl std::fmt::Display for Score {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "({}, {})", self.mg(), self.eg())
+	}
+}
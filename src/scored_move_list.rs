@@ -1,31 +1,44 @@
-// NOT BEING USED
-
+use crate::move_list::{MoveList, MAX_MOVES};
 use crate::MoveData;
 
+// The move generator writes straight into `moves`, and the scores are filled in afterwards
 pub struct ScoredMoveList {
-	pub pairs: Vec<(i32, MoveData)>,
+	moves: MoveList,
+	scores: [i32; MAX_MOVES],
 }
 
 impl ScoredMoveList {
 	pub fn new() -> Self {
 		Self {
-			pairs: vec![],
+			moves: MoveList::new(),
+			scores: [0; MAX_MOVES],
 		}
 	}
 
-	pub fn len(&self) -> usize { self.pairs.len() }
+	pub fn len(&self) -> usize { self.moves.len() }
+	pub fn moves_mut(&mut self) -> &mut MoveList { &mut self.moves }
+
+	pub fn get(&self, index: usize) -> MoveData { self.moves.get(index) }
+	pub fn score(&self, index: usize) -> i32 { self.scores[index] }
+	pub fn set_score(&mut self, index: usize, score: i32) { self.scores[index] = score; }
 
-	pub fn push(&mut self, score: i32, move_data: MoveData) {
-		self.pairs.push((score, move_data));
+	pub fn remove(&mut self, index: usize) {
+		self.scores[index] = self.scores[self.moves.len() - 1];
+		self.moves.swap_remove(index);
 	}
 
-	pub fn get(&mut self, move_index: usize) -> MoveData {
-		for i in (move_index + 1)..self.pairs.len() {
-			if self.pairs[i].0 > self.pairs[move_index].0 {
-				self.pairs.swap(move_index, i);
+	// Selection sort, one move at a time: only the moves that actually get searched are sorted
+	pub fn pick(&mut self, move_index: usize, end: usize) -> MoveData {
+		let mut best_index = move_index;
+		for i in (move_index + 1)..end {
+			if self.scores[i] > self.scores[best_index] {
+				best_index = i;
 			}
 		}
 
-		self.pairs[move_index].1
+		self.scores.swap(move_index, best_index);
+		self.moves.swap(move_index, best_index);
+
+		self.moves.get(move_index)
 	}
-}
\ No newline at end of file
+}
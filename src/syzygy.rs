@@ -0,0 +1,1548 @@
+/*
+Syzygy tablebase probing
+
+This reads the .rtbw (win / draw / loss) and .rtbz (distance to zeroing) files directly, following the same
+layout as the probing code in Fathom / Stockfish. Tables are only opened the first time they're needed, and
+then memory mapped, so probing doesn't do any reads or allocations, and even the huge 6-man files don't need to fit in memory
+(without mmap, on anything that isn't unix, the whole file gets read in instead)
+
+Syzygy squares go from a1 = 0 to h8 = 63, which is just Maxwell's index ^ 56,
+and pieces are 1-6 for white pawn-king and 9-14 for black pawn-king
+*/
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use crate::board::MoveGenType;
+use crate::move_list::MoveList;
+use crate::pieces::*;
+use crate::Board;
+
+// Far above any normal evaluation, but still below the checkmate scores
+pub const TB_WIN_EVAL: i32 = 50000;
+
+pub const WDL_LOSS:         i32 = -2;
+pub const WDL_BLESSED_LOSS: i32 = -1; // Loss, but the 50 move rule saves it
+pub const WDL_DRAW:         i32 = 0;
+pub const WDL_CURSED_WIN:   i32 = 1; // Win, but the 50 move rule gets in the way
+pub const WDL_WIN:          i32 = 2;
+
+const MAX_TB_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// Flags stored for each (side, file) part of a table
+const FLAG_STM:          u8 = 1;
+const FLAG_MAPPED:       u8 = 2;
+const FLAG_WIN_PLIES:    u8 = 4;
+const FLAG_LOSS_PLIES:   u8 = 8;
+const FLAG_WIDE:         u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum ProbeState {
+	Ok,
+	Fail,
+	ChangeStm, // DTZ tables only store one side to move
+	ZeroingBestMove, // The best move is a capture or pawn move, so the DTZ table can't be trusted
+}
+
+fn file_of(square: usize) -> usize { square & 7 }
+fn rank_of(square: usize) -> usize { square >> 3 }
+fn off_diagonal(square: usize) -> i32 { rank_of(square) as i32 - file_of(square) as i32 }
+fn flip_diagonal(square: usize) -> usize { ((square >> 3) | (square << 3)) & 63 }
+
+// "KRPvKR" -> [[pawns, knights, ...] for the first side, [...] for the second side]
+fn parse_table_name(name: &str) -> Option<[[u8; 6]; 2]> {
+	let counts = parse_material(name)?;
+
+	let piece_count = counts.iter().flatten().map(|c| *c as usize).sum::<usize>();
+	if piece_count > MAX_TB_PIECES {
+		return None;
+	}
+
+	Some(counts)
+}
+
+// Big endian, and reads past the end of the file are zeroes, so that decoding the last block doesn't fail
+fn read_be(data: &[u8], offset: usize, bytes: usize) -> u64 {
+	(0..bytes).fold(0, |value, i| value << 8 | *data.get(offset + i).unwrap_or(&0) as u64)
+}
+
+fn read_le(data: &[u8], offset: u64, bytes: usize) -> Option<u64> {
+	let bytes = data.get(offset as usize..offset as usize + bytes)?;
+	Some(bytes.iter().rev().fold(0, |value, byte| value << 8 | *byte as u64))
+}
+
+// A whole table file, mapped into memory once and then only read from
+struct TableData {
+	#[cfg(unix)]
+	pointer: *mut libc::c_void,
+	#[cfg(unix)]
+	length: usize,
+
+	#[cfg(not(unix))]
+	bytes: Vec<u8>,
+}
+
+// The mapping is read-only, so it can be shared between threads like a Vec
+unsafe impl Send for TableData {}
+unsafe impl Sync for TableData {}
+
+impl TableData {
+	#[cfg(unix)]
+	fn open(path: &Path) -> Option<Self> {
+		use std::os::unix::io::AsRawFd;
+
+		let file = File::open(path).ok()?;
+		let length = file.metadata().ok()?.len() as usize;
+		if length == 0 {
+			return None;
+		}
+
+		// The file can be closed right after, the mapping stays valid until munmap
+		let pointer = unsafe { libc::mmap(std::ptr::null_mut(), length, libc::PROT_READ, libc::MAP_SHARED, file.as_raw_fd(), 0) };
+		if pointer == libc::MAP_FAILED {
+			return None;
+		}
+
+		// Probes jump all over the file, so reading ahead is wasted
+		unsafe { libc::madvise(pointer, length, libc::MADV_RANDOM) };
+
+		Some(Self { pointer, length })
+	}
+
+	#[cfg(not(unix))]
+	fn open(path: &Path) -> Option<Self> {
+		Some(Self { bytes: std::fs::read(path).ok()? })
+	}
+
+	#[cfg(unix)]
+	fn bytes(&self) -> &[u8] {
+		unsafe { std::slice::from_raw_parts(self.pointer as *const u8, self.length) }
+	}
+
+	#[cfg(not(unix))]
+	fn bytes(&self) -> &[u8] {
+		&self.bytes
+	}
+}
+
+#[cfg(unix)]
+impl Drop for TableData {
+	fn drop(&mut self) {
+		unsafe { libc::munmap(self.pointer, self.length) };
+	}
+}
+
+// Reads the table header from the start of the file, keeping track of the offset for the alignment rules
+struct HeaderReader<'a> {
+	data: &'a [u8],
+	offset: u64,
+}
+
+impl HeaderReader<'_> {
+	fn u8(&mut self) -> Option<u8> {
+		let byte = *self.data.get(self.offset as usize)?;
+		self.offset += 1;
+		Some(byte)
+	}
+
+	fn u16(&mut self) -> Option<u16> {
+		Some(u16::from_le_bytes([self.u8()?, self.u8()?]))
+	}
+
+	fn u32(&mut self) -> Option<u32> {
+		Some(u32::from_le_bytes([self.u8()?, self.u8()?, self.u8()?, self.u8()?]))
+	}
+
+	fn skip(&mut self, bytes: u64) -> Option<()> {
+		self.offset += bytes;
+		Some(())
+	}
+
+	fn align(&mut self, alignment: u64) -> Option<()> {
+		let padding = (alignment - self.offset % alignment) % alignment;
+		self.skip(padding)
+	}
+}
+
+// Precalculated tables used to turn a position into an index
+struct Encoding {
+	map_pawns: [usize; 64],
+	map_b1h1h7: [usize; 64],
+	map_a1d1d4: [usize; 64],
+	map_kk: [[usize; 64]; 10],
+	binomial: [[u64; 64]; MAX_TB_PIECES],
+	lead_pawn_index: [[u64; 64]; MAX_TB_PIECES],
+	lead_pawns_size: [[u64; 4]; MAX_TB_PIECES],
+}
+
+impl Encoding {
+	fn new() -> Self {
+		let mut encoding = Self {
+			map_pawns: [0; 64],
+			map_b1h1h7: [0; 64],
+			map_a1d1d4: [0; 64],
+			map_kk: [[0; 64]; 10],
+			binomial: [[0; 64]; MAX_TB_PIECES],
+			lead_pawn_index: [[0; 64]; MAX_TB_PIECES],
+			lead_pawns_size: [[0; 4]; MAX_TB_PIECES],
+		};
+
+		// Squares below the a1-h8 diagonal -> 0..27
+		let mut code = 0;
+		for square in 0..64 {
+			if off_diagonal(square) < 0 {
+				encoding.map_b1h1h7[square] = code;
+				code += 1;
+			}
+		}
+
+		// Squares in the a1-d1-d4 triangle -> 0..9, with the ones on the diagonal last
+		let mut diagonal = vec![];
+		code = 0;
+		for square in 0..=27 {
+			if off_diagonal(square) < 0
+			&& file_of(square) <= 3 {
+				encoding.map_a1d1d4[square] = code;
+				code += 1;
+			} else if off_diagonal(square) == 0
+			&& file_of(square) <= 3 {
+				diagonal.push(square);
+			}
+		}
+
+		for square in diagonal {
+			encoding.map_a1d1d4[square] = code;
+			code += 1;
+		}
+
+		// Every legal placement of the two kings, with the first one in the a1-d1-d4 triangle
+		let mut both_on_diagonal = vec![];
+		code = 0;
+		for index in 0..10 {
+			for first in 0..=27 {
+				if encoding.map_a1d1d4[first] != index
+				|| (index == 0 && first != 1) { // b1 is mapped to 0
+					continue;
+				}
+
+				for second in 0..64 {
+					let touching =
+						   (rank_of(first) as i32 - rank_of(second) as i32).abs() <= 1
+						&& (file_of(first) as i32 - file_of(second) as i32).abs() <= 1;
+
+					if touching
+					|| (off_diagonal(first) == 0 && off_diagonal(second) > 0) {
+						continue;
+					}
+
+					if off_diagonal(first) == 0
+					&& off_diagonal(second) == 0 {
+						both_on_diagonal.push((index, second));
+					} else {
+						encoding.map_kk[index][second] = code;
+						code += 1;
+					}
+				}
+			}
+		}
+
+		for (index, second) in both_on_diagonal {
+			encoding.map_kk[index][second] = code;
+			code += 1;
+		}
+
+		// binomial[k][n] = ways to choose k squares out of n
+		encoding.binomial[0][0] = 1;
+		for n in 1..64 {
+			for k in 0..MAX_TB_PIECES {
+				if k > n {
+					break;
+				}
+
+				encoding.binomial[k][n] =
+					  (if k > 0 { encoding.binomial[k - 1][n - 1] } else { 0 })
+					+ (if k < n { encoding.binomial[k][n - 1] } else { 0 });
+			}
+		}
+
+		// Pawns on a2-h7 -> 0..47, where the leading pawn is the one with the highest value:
+		// closest to the edge, then lowest rank
+		let mut mapped_pairs = 0;
+		for lead_pawns in 1..=5 {
+			for file in 0..4 {
+				let mut index = 0;
+
+				for rank in 1..7 {
+					let square = rank * 8 + file;
+
+					if lead_pawns == 1 {
+						encoding.map_pawns[square] = 47 - 2 * mapped_pairs;
+						encoding.map_pawns[square ^ 7] = 46 - 2 * mapped_pairs;
+						mapped_pairs += 1;
+					}
+
+					encoding.lead_pawn_index[lead_pawns][square] = index;
+					index += encoding.binomial[lead_pawns - 1][encoding.map_pawns[square]];
+				}
+
+				encoding.lead_pawns_size[lead_pawns][file] = index;
+			}
+		}
+
+		encoding
+	}
+}
+
+// Indexing and decompression info for one side to move (and leading pawn file) of a table
+#[derive(Clone, Default)]
+struct PairsData {
+	flags: u8,
+	min_symbol_length: u8,
+	block_size: u64,
+	span: u64,
+	block_count: u64,
+	block_lengths_size: u64,
+	sparse_index_size: u64,
+
+	// Offsets into the file
+	sparse_index: u64,
+	block_lengths: u64,
+	data: u64,
+
+	lowest_symbol: Vec<u16>,
+	base64: Vec<u64>,
+	symbol_lengths: Vec<u8>,
+	symbol_tree: Vec<[u16; 2]>,
+
+	pieces: [u8; MAX_TB_PIECES],
+	group_index: [u64; MAX_TB_PIECES + 1],
+	group_length: [usize; MAX_TB_PIECES + 1],
+	map_index: [u64; 4], // Offsets into the DTZ map for win, loss, cursed win and blessed loss
+}
+
+struct Table {
+	path: PathBuf,
+	is_dtz: bool,
+	data: Option<TableData>,
+	loaded: bool,
+	failed: bool,
+
+	key: u64,
+	key2: u64,
+	piece_count: usize,
+	has_pawns: bool,
+	has_unique_pieces: bool,
+	pawn_count: [usize; 2], // Leading color, other color
+
+	items: [[PairsData; 4]; 2], // [side to move][leading pawn file]
+	dtz_map: Vec<u8>,
+}
+
+impl Table {
+	fn new(path: PathBuf, is_dtz: bool, counts: [[u8; 6]; 2]) -> Self {
+		let pawns = [counts[0][PAWN] as usize, counts[1][PAWN] as usize];
+
+		// The side with fewer pawns leads, because that compresses better
+		let first_leads = pawns[1] == 0 || (pawns[0] > 0 && pawns[1] >= pawns[0]);
+
+		Self {
+			path,
+			is_dtz,
+			data: None,
+			loaded: false,
+			failed: false,
+
+			key: material_key(&counts),
+			key2: material_key(&[counts[1], counts[0]]),
+			piece_count: counts.iter().flatten().map(|c| *c as usize).sum(),
+			has_pawns: pawns[0] + pawns[1] > 0,
+			has_unique_pieces: counts.iter().any(|side| side[PAWN..KING].contains(&1)),
+			pawn_count:
+				if first_leads {
+					[pawns[0], pawns[1]]
+				} else {
+					[pawns[1], pawns[0]]
+				},
+
+			items: Default::default(),
+			dtz_map: vec![],
+		}
+	}
+
+	fn get(&self, side: usize, file: usize) -> &PairsData {
+		&self.items[if self.is_dtz { 0 } else { side }][if self.has_pawns { file } else { 0 }]
+	}
+
+	// Tables are only read the first time they get probed
+	fn ensure_loaded(&mut self, encoding: &Encoding) -> bool {
+		if !self.loaded
+		&& !self.failed {
+			if self.load(encoding).is_some() {
+				self.loaded = true;
+			} else {
+				println!("info string Failed to load tablebase {}", self.path.display());
+				self.failed = true;
+				self.data = None;
+			}
+		}
+
+		self.loaded
+	}
+
+	fn load(&mut self, encoding: &Encoding) -> Option<()> {
+		let data = TableData::open(&self.path)?;
+		let mut reader = HeaderReader {
+			data: data.bytes(),
+			offset: 0,
+		};
+
+		let magic = [reader.u8()?, reader.u8()?, reader.u8()?, reader.u8()?];
+		if magic != if self.is_dtz { DTZ_MAGIC } else { WDL_MAGIC } {
+			return None;
+		}
+
+		let header_flags = reader.u8()?;
+		if (header_flags & 2 != 0) != self.has_pawns {
+			return None;
+		}
+
+		let sides = if !self.is_dtz && self.key != self.key2 { 2 } else { 1 };
+		let max_file = if self.has_pawns { 3 } else { 0 };
+		let pawns_on_both_sides = self.has_pawns && self.pawn_count[1] > 0;
+
+		for file in 0..=max_file {
+			let first = reader.u8()?;
+			let second = if pawns_on_both_sides { reader.u8()? } else { 0 };
+
+			let order = [
+				[first & 0xF, if pawns_on_both_sides { second & 0xF } else { 0xF }],
+				[first >> 4,  if pawns_on_both_sides { second >> 4 } else { 0xF }],
+			];
+
+			for k in 0..self.piece_count {
+				let pieces = reader.u8()?;
+				for side in 0..sides {
+					self.items[side][file].pieces[k] = if side == 1 { pieces >> 4 } else { pieces & 0xF };
+				}
+			}
+
+			for (side, order) in order.iter().copied().enumerate().take(sides) {
+				self.set_groups(encoding, side, file, order);
+			}
+		}
+
+		reader.align(2)?;
+
+		for file in 0..=max_file {
+			for side in 0..sides {
+				Self::set_sizes(&mut self.items[side][file], &mut reader)?;
+			}
+		}
+
+		if self.is_dtz {
+			let map_start = reader.offset;
+
+			for file in 0..=max_file {
+				let flags = self.items[0][file].flags;
+				if flags & FLAG_MAPPED == 0 {
+					continue;
+				}
+
+				for i in 0..4 {
+					if flags & FLAG_WIDE != 0 {
+						reader.align(2)?;
+						self.items[0][file].map_index[i] = reader.offset - map_start + 2;
+						let length = reader.u16()? as u64;
+						reader.skip(length * 2)?;
+					} else {
+						self.items[0][file].map_index[i] = reader.offset - map_start + 1;
+						let length = reader.u8()? as u64;
+						reader.skip(length)?;
+					}
+				}
+			}
+
+			reader.align(2)?;
+
+			self.dtz_map = reader.data.get(map_start as usize..reader.offset as usize)?.to_vec();
+		}
+
+		// Everything after this point is read from the file while probing
+		let mut offset = reader.offset;
+
+		for file in 0..=max_file {
+			for side in 0..sides {
+				self.items[side][file].sparse_index = offset;
+				offset += self.items[side][file].sparse_index_size * 6;
+			}
+		}
+
+		for file in 0..=max_file {
+			for side in 0..sides {
+				self.items[side][file].block_lengths = offset;
+				offset += self.items[side][file].block_lengths_size * 2;
+			}
+		}
+
+		for file in 0..=max_file {
+			for side in 0..sides {
+				offset = (offset + 0x3F) & !0x3F;
+				self.items[side][file].data = offset;
+				offset += self.items[side][file].block_count * self.items[side][file].block_size;
+			}
+		}
+
+		self.data = Some(data);
+		Some(())
+	}
+
+	/*
+	Pieces that get encoded together are grouped: the leading group is the leading pawns, or
+	the first 3 unique pieces (or just the kings if there aren't any), then each group after that is
+	pieces of the same type and color. The order the groups get multiplied in is stored in the file
+	*/
+	fn set_groups(&mut self, encoding: &Encoding, side: usize, file: usize, order: [u8; 2]) {
+		let has_pawns = self.has_pawns;
+		let has_unique_pieces = self.has_unique_pieces;
+		let pawn_count = self.pawn_count;
+		let piece_count = self.piece_count;
+		let d = &mut self.items[side][file];
+
+		let mut n = 0;
+		let mut first_length: i32 =
+			if has_pawns {
+				0
+			} else if has_unique_pieces {
+				3
+			} else {
+				2
+			};
+
+		d.group_length[0] = 1;
+		for i in 1..piece_count {
+			first_length -= 1;
+			if first_length > 0
+			|| d.pieces[i] == d.pieces[i - 1] {
+				d.group_length[n] += 1;
+			} else {
+				n += 1;
+				d.group_length[n] = 1;
+			}
+		}
+
+		n += 1;
+		d.group_length[n] = 0;
+
+		let pawns_on_both_sides = has_pawns && pawn_count[1] > 0;
+		let mut next = if pawns_on_both_sides { 2 } else { 1 };
+		let mut free_squares = 64 - d.group_length[0] - if pawns_on_both_sides { d.group_length[1] } else { 0 };
+		let mut index = 1;
+
+		let mut k = 0;
+		while next < n
+		|| k == order[0]
+		|| k == order[1] {
+			if k == order[0] {
+				d.group_index[0] = index;
+				index *=
+					if has_pawns {
+						encoding.lead_pawns_size[d.group_length[0]][file]
+					} else if has_unique_pieces {
+						31332
+					} else {
+						462
+					};
+			} else if k == order[1] {
+				d.group_index[1] = index;
+				index *= encoding.binomial[d.group_length[1]][48 - d.group_length[0]];
+			} else {
+				d.group_index[next] = index;
+				index *= encoding.binomial[d.group_length[next]][free_squares];
+				free_squares -= d.group_length[next];
+				next += 1;
+			}
+
+			k += 1;
+		}
+
+		d.group_index[n] = index;
+	}
+
+	// Reads the Huffman code and the pairs used by the compression
+	fn set_sizes(d: &mut PairsData, reader: &mut HeaderReader) -> Option<()> {
+		d.flags = reader.u8()?;
+
+		if d.flags & FLAG_SINGLE_VALUE != 0 {
+			d.min_symbol_length = reader.u8()?; // This is the value of every position
+			return Some(());
+		}
+
+		let groups = d.group_length.iter().position(|length| *length == 0)?;
+		let table_size = d.group_index[groups];
+
+		d.block_size = 1 << reader.u8()?;
+		d.span = 1 << reader.u8()?;
+		d.sparse_index_size = table_size.div_ceil(d.span);
+		let padding = reader.u8()? as u64;
+		d.block_count = reader.u32()? as u64;
+		d.block_lengths_size = d.block_count + padding;
+
+		let max_symbol_length = reader.u8()?;
+		d.min_symbol_length = reader.u8()?;
+		if max_symbol_length < d.min_symbol_length {
+			return None;
+		}
+
+		let lengths = (max_symbol_length - d.min_symbol_length) as usize + 1;
+		d.lowest_symbol = vec![0; lengths];
+		for i in 0..lengths {
+			d.lowest_symbol[i] = reader.u16()?;
+		}
+
+		// Longer codes have lower values, so base64[i] is the lowest code of length i + min_symbol_length,
+		// padded out to 64 bits
+		d.base64 = vec![0; lengths];
+		for i in (0..lengths - 1).rev() {
+			d.base64[i] = d.base64[i + 1]
+				.wrapping_add(d.lowest_symbol[i] as u64)
+				.wrapping_sub(d.lowest_symbol[i + 1] as u64) / 2;
+		}
+
+		for i in 0..lengths {
+			d.base64[i] = d.base64[i].checked_shl(64 - i as u32 - d.min_symbol_length as u32).unwrap_or(0);
+		}
+
+		let symbol_count = reader.u16()? as usize;
+		d.symbol_tree = Vec::with_capacity(symbol_count);
+		for _ in 0..symbol_count {
+			let (a, b, c) = (reader.u8()? as u16, reader.u8()? as u16, reader.u8()? as u16);
+			d.symbol_tree.push([((b & 0xF) << 8) | a, (c << 4) | (b >> 4)]);
+		}
+
+		if symbol_count & 1 != 0 {
+			reader.skip(1)?;
+		}
+
+		// Each symbol stands for a pair of symbols, so this is the number of values (- 1) that each symbol expands to
+		d.symbol_lengths = vec![0; symbol_count];
+		let mut visited = vec![false; symbol_count];
+		for symbol in 0..symbol_count {
+			if !visited[symbol] {
+				d.symbol_lengths[symbol] = Self::set_symbol_length(d, symbol, &mut visited)?;
+			}
+		}
+
+		Some(())
+	}
+
+	fn set_symbol_length(d: &mut PairsData, symbol: usize, visited: &mut Vec<bool>) -> Option<u8> {
+		visited[symbol] = true;
+
+		let [left, right] = d.symbol_tree[symbol];
+		if right == 0xFFF {
+			return Some(0);
+		}
+
+		let (left, right) = (left as usize, right as usize);
+		if left >= visited.len()
+		|| right >= visited.len() {
+			return None;
+		}
+
+		if !visited[left] {
+			d.symbol_lengths[left] = Self::set_symbol_length(d, left, visited)?;
+		}
+
+		if !visited[right] {
+			d.symbol_lengths[right] = Self::set_symbol_length(d, right, visited)?;
+		}
+
+		Some(d.symbol_lengths[left].wrapping_add(d.symbol_lengths[right]).wrapping_add(1))
+	}
+
+	fn decompress_pairs(data: &[u8], d: &PairsData, index: u64) -> Option<usize> {
+		if d.flags & FLAG_SINGLE_VALUE != 0 {
+			return Some(d.min_symbol_length as usize);
+		}
+
+		// The sparse index points at a known block and offset every span values, so we start there
+		let entry = d.sparse_index + index / d.span * 6;
+		let mut block = read_le(data, entry, 4)?;
+		let mut offset = read_le(data, entry + 4, 2)? as i64;
+		offset += (index % d.span) as i64 - (d.span / 2) as i64;
+
+		let block_length = |block: u64| -> Option<i64> {
+			Some(read_le(data, d.block_lengths + block * 2, 2)? as i64)
+		};
+
+		while offset < 0 {
+			block = block.checked_sub(1)?;
+			offset += block_length(block)? + 1;
+		}
+
+		loop {
+			let length = block_length(block)?;
+			if offset <= length {
+				break;
+			}
+
+			offset -= length + 1;
+			block += 1;
+		}
+
+		let block_start = (d.data + block * d.block_size) as usize;
+		if block_start >= data.len() {
+			return None;
+		}
+
+		let mut bits = read_be(data, block_start, 8);
+		let mut bits_left = 64;
+		let mut next_byte = 8;
+
+		// Find the symbol that contains our offset
+		let mut symbol;
+		loop {
+			let mut length = 0;
+			while bits < d.base64[length] {
+				length += 1;
+			}
+
+			symbol = ((bits - d.base64[length]) >> (64 - length - d.min_symbol_length as usize)) as usize;
+			symbol += d.lowest_symbol[length] as usize;
+
+			let symbol_length = *d.symbol_lengths.get(symbol)? as i64;
+			if offset < symbol_length + 1 {
+				break;
+			}
+
+			offset -= symbol_length + 1;
+			length += d.min_symbol_length as usize;
+			bits <<= length;
+			bits_left -= length;
+
+			if bits_left <= 32 {
+				bits_left += 32;
+				bits |= read_be(data, block_start + next_byte, 4) << (64 - bits_left);
+				next_byte += 4;
+			}
+		}
+
+		// Then expand the pairs until we get to a single value
+		while d.symbol_lengths[symbol] != 0 {
+			let left = d.symbol_tree[symbol][0] as usize;
+			let left_length = *d.symbol_lengths.get(left)? as i64;
+
+			if offset < left_length + 1 {
+				symbol = left;
+			} else {
+				offset -= left_length + 1;
+				symbol = d.symbol_tree[symbol][1] as usize;
+			}
+		}
+
+		Some(d.symbol_tree[symbol][0] as usize)
+	}
+
+	// DTZ values are stored sorted by how often they happen, so they have to be mapped back
+	fn map_dtz(&self, file: usize, value: usize, wdl: i32) -> Option<i32> {
+		const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+
+		let d = self.get(0, file);
+		let mut value = value;
+
+		if d.flags & FLAG_MAPPED != 0 {
+			let index = d.map_index[WDL_MAP[(wdl + 2) as usize]] as usize;
+
+			value =
+				if d.flags & FLAG_WIDE != 0 {
+					let i = index + value * 2;
+					u16::from_le_bytes([*self.dtz_map.get(i)?, *self.dtz_map.get(i + 1)?]) as usize
+				} else {
+					*self.dtz_map.get(index + value)? as usize
+				};
+		}
+
+		// Some tables store moves instead of plies
+		let mut value = value as i32;
+		if (wdl == WDL_WIN && d.flags & FLAG_WIN_PLIES == 0)
+		|| (wdl == WDL_LOSS && d.flags & FLAG_LOSS_PLIES == 0)
+		|| wdl == WDL_CURSED_WIN
+		|| wdl == WDL_BLESSED_LOSS {
+			value *= 2;
+		}
+
+		Some(value + 1)
+	}
+}
+
+pub struct Syzygy {
+	encoding: Encoding,
+
+	wdl_tables: Vec<Table>,
+	dtz_tables: Vec<Table>,
+	wdl_keys: HashMap<u64, usize>,
+	dtz_keys: HashMap<u64, usize>,
+
+	pub max_pieces: usize,
+	pub hits: u128,
+}
+
+impl Syzygy {
+	pub fn empty() -> Self {
+		Self {
+			encoding: Encoding::new(),
+
+			wdl_tables: vec![],
+			dtz_tables: vec![],
+			wdl_keys: HashMap::new(),
+			dtz_keys: HashMap::new(),
+
+			max_pieces: 0,
+			hits: 0,
+		}
+	}
+
+	// Multiple folders can be given, separated like the PATH variable (":" or ";" on Windows)
+	pub fn new(paths: &str) -> Self {
+		let mut syzygy = Self::empty();
+
+		if paths.is_empty()
+		|| paths == "<empty>" {
+			return syzygy;
+		}
+
+		for folder in std::env::split_paths(paths) {
+			syzygy.add_folder(&folder);
+		}
+
+		if syzygy.max_pieces > 0 {
+			println!("info string Found {} WDL and {} DTZ tablebases, up to {} pieces", syzygy.wdl_tables.len(), syzygy.dtz_tables.len(), syzygy.max_pieces);
+		}
+
+		syzygy
+	}
+
+	fn add_folder(&mut self, folder: &Path) {
+		let Ok(entries) = std::fs::read_dir(folder) else {
+			println!("info string Couldn't read tablebase folder {}", folder.display());
+			return;
+		};
+
+		for entry in entries.flatten() {
+			let path = entry.path();
+
+			let (Some(name), Some(extension)) = (path.file_stem().and_then(|s| s.to_str()), path.extension().and_then(|s| s.to_str())) else {
+				continue;
+			};
+
+			let is_dtz = match extension {
+				"rtbw" => false,
+				"rtbz" => true,
+				_ => continue,
+			};
+
+			let Some(counts) = parse_table_name(name) else {
+				continue;
+			};
+
+			let (tables, keys) =
+				if is_dtz {
+					(&mut self.dtz_tables, &mut self.dtz_keys)
+				} else {
+					(&mut self.wdl_tables, &mut self.wdl_keys)
+				};
+
+			let table = Table::new(path, is_dtz, counts);
+			if keys.contains_key(&table.key) {
+				continue;
+			}
+
+			keys.insert(table.key, tables.len());
+			keys.insert(table.key2, tables.len());
+
+			if !is_dtz {
+				self.max_pieces = usize::max(self.max_pieces, table.piece_count);
+			}
+
+			tables.push(table);
+		}
+	}
+
+	pub fn can_probe(&self, board: &Board) -> bool {
+		   self.max_pieces > 0
+		&& board.board_state.current.castling_rights == 0
+		&& board.occupied_bitboard().count_ones() as usize <= self.max_pieces
+	}
+
+	// Returns one of WDL_LOSS..=WDL_WIN for the side to move, assuming the fifty move counter was just reset
+	pub fn probe_wdl(&mut self, board: &mut Board) -> Option<i32> {
+		if !self.can_probe(board) {
+			return None;
+		}
+
+		let mut state = ProbeState::Ok;
+		let wdl = self.search(board, false, &mut state);
+
+		if state == ProbeState::Fail {
+			return None;
+		}
+
+		self.hits += 1;
+		Some(wdl)
+	}
+
+	/*
+	Returns the number of plies until the next capture or pawn move, with the sign of the result:
+	positive for a win, negative for a loss and 0 for a draw. Values above 100 are cursed wins / blessed losses
+	*/
+	pub fn probe_dtz(&mut self, board: &mut Board) -> Option<i32> {
+		if !self.can_probe(board) {
+			return None;
+		}
+
+		let mut state = ProbeState::Ok;
+		let dtz = self.dtz(board, &mut state);
+
+		if state == ProbeState::Fail {
+			return None;
+		}
+
+		self.hits += 1;
+		Some(dtz)
+	}
+
+	/*
+	Uses DTZ to only keep the moves that hold on to the result: the winning moves that stay within the 50 move rule,
+	the moves that keep the draw, or if everything loses, all the moves unless the 50 move rule is close
+	*/
+	pub fn root_moves(&mut self, board: &mut Board) -> Option<MoveList> {
+		if !self.can_probe(board) {
+			return None;
+		}
+
+		let fifty_move_counter = board.board_state.current.fifty_move_counter as i32;
+		let mut state = ProbeState::Ok;
+
+		let mut moves = MoveList::new();
+		board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+
+		let mut root_moves = vec![];
+		for m in moves.iter().copied() {
+			if !board.make_move(m) {
+				continue;
+			}
+
+			let mut dtz =
+				if board.board_state.current.fifty_move_counter == 0 {
+					// After a capture or pawn move the DTZ is one of -101, -1, 0, 1, 101
+					dtz_before_zeroing(-self.search(board, false, &mut state))
+				} else {
+					let dtz = -self.dtz(board, &mut state);
+					dtz + dtz.signum()
+				};
+
+			// Checkmate is always the best move
+			if dtz == 2
+			&& board.king_in_check(board.white_to_move)
+			&& !self.has_legal_moves(board) {
+				dtz = 1;
+			}
+
+			board.undo_last_move();
+
+			if state == ProbeState::Fail {
+				return None;
+			}
+
+			root_moves.push((m, dtz));
+		}
+
+		self.hits += 1;
+
+		let keep: Vec<bool> =
+			if let Some(best) = root_moves.iter().map(|(_, dtz)| *dtz).filter(|dtz| *dtz > 0).min() {
+				// If the fastest win fits inside the 50 move rule, then any win that also fits is fine
+				let max = if best + fifty_move_counter <= 99 { 99 - fifty_move_counter } else { best };
+				root_moves.iter().map(|(_, dtz)| *dtz > 0 && *dtz <= max).collect()
+			} else if root_moves.iter().any(|(_, dtz)| *dtz == 0) {
+				root_moves.iter().map(|(_, dtz)| *dtz == 0).collect()
+			} else {
+				let best = root_moves.iter().map(|(_, dtz)| *dtz).min().unwrap_or(0);
+				root_moves.iter().map(|(_, dtz)| -best * 2 + fifty_move_counter < 100 || *dtz == best).collect()
+			};
+
+		let mut filtered = MoveList::new();
+		for (i, (m, _)) in root_moves.iter().enumerate() {
+			if keep[i] {
+				filtered.push(*m);
+			}
+		}
+
+		Some(filtered)
+	}
+
+	fn has_legal_moves(&self, board: &mut Board) -> bool {
+		let mut moves = MoveList::new();
+		board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+
+		for m in moves.iter().copied() {
+			if board.make_move(m) {
+				board.undo_last_move();
+				return true;
+			}
+		}
+
+		false
+	}
+
+	/*
+	Tables don't store correct values for positions where a capture wins (or the position has en passant),
+	because the generator is allowed to put whatever compresses best there. So captures (and pawn moves for DTZ)
+	are searched first, and the best of those and the stored value is the real result
+	*/
+	fn search(&mut self, board: &mut Board, check_zeroing_moves: bool, state: &mut ProbeState) -> i32 {
+		let mut best = WDL_LOSS;
+
+		let mut moves = MoveList::new();
+		board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+
+		let mut legal_moves = 0;
+		let mut searched = 0;
+		for m in moves.iter().copied() {
+			if !board.make_move(m) {
+				continue;
+			}
+
+			legal_moves += 1;
+
+			if m.capture == NO_PIECE as u8
+			&& (!check_zeroing_moves || get_piece_type(m.piece as usize) != PAWN) {
+				board.undo_last_move();
+				continue;
+			}
+
+			searched += 1;
+
+			let wdl = -self.search(board, false, state);
+			board.undo_last_move();
+
+			if *state == ProbeState::Fail {
+				return WDL_DRAW;
+			}
+
+			if wdl > best {
+				best = wdl;
+
+				if wdl >= WDL_WIN {
+					*state = ProbeState::ZeroingBestMove;
+					return wdl;
+				}
+			}
+		}
+
+		// If every legal move was searched, the stored value doesn't matter
+		let no_more_moves = searched > 0 && searched == legal_moves;
+
+		let wdl =
+			if no_more_moves {
+				best
+			} else {
+				let wdl = self.probe_table(board, false, WDL_DRAW, state);
+				if *state == ProbeState::Fail {
+					return WDL_DRAW;
+				}
+				wdl
+			};
+
+		if best >= wdl {
+			*state =
+				if best > WDL_DRAW
+				|| no_more_moves {
+					ProbeState::ZeroingBestMove
+				} else {
+					ProbeState::Ok
+				};
+
+			return best;
+		}
+
+		*state = ProbeState::Ok;
+		wdl
+	}
+
+	fn dtz(&mut self, board: &mut Board, state: &mut ProbeState) -> i32 {
+		*state = ProbeState::Ok;
+		let wdl = self.search(board, true, state);
+
+		// Draws aren't stored
+		if *state == ProbeState::Fail
+		|| wdl == WDL_DRAW {
+			return 0;
+		}
+
+		if *state == ProbeState::ZeroingBestMove {
+			return dtz_before_zeroing(wdl);
+		}
+
+		let dtz = self.probe_table(board, true, wdl, state);
+
+		if *state == ProbeState::Fail {
+			return 0;
+		}
+
+		if *state != ProbeState::ChangeStm {
+			let cursed = if wdl == WDL_CURSED_WIN || wdl == WDL_BLESSED_LOSS { 100 } else { 0 };
+			return (dtz + cursed) * wdl.signum();
+		}
+
+		// The table only has the other side to move, so search 1 ply and take the best DTZ
+		let mut min_dtz = 0xFFFF;
+
+		let mut moves = MoveList::new();
+		board.get_pseudo_legal_moves_for_color(board.white_to_move, MoveGenType::All, &mut moves);
+
+		for m in moves.iter().copied() {
+			if !board.make_move(m) {
+				continue;
+			}
+
+			let zeroing = m.capture != NO_PIECE as u8 || get_piece_type(m.piece as usize) == PAWN;
+
+			let mut dtz =
+				if zeroing {
+					-dtz_before_zeroing(self.search(board, false, state))
+				} else {
+					-self.dtz(board, state)
+				};
+
+			if dtz == 1
+			&& board.king_in_check(board.white_to_move)
+			&& !self.has_legal_moves(board) {
+				min_dtz = 1;
+			}
+
+			if !zeroing {
+				dtz += dtz.signum();
+			}
+
+			if dtz < min_dtz
+			&& dtz.signum() == wdl.signum() {
+				min_dtz = dtz;
+			}
+
+			board.undo_last_move();
+
+			if *state == ProbeState::Fail {
+				return 0;
+			}
+		}
+
+		// No legal moves means we're getting checkmated
+		if min_dtz == 0xFFFF { -1 } else { min_dtz }
+	}
+
+	/*
+	To encode k pieces of the same type and color, the squares get sorted s1 < s2 < ... < sk, then
+	index = binomial[1][s1] + binomial[2][s2] + ... + binomial[k][sk]
+	*/
+	fn probe_table(&mut self, board: &Board, is_dtz: bool, wdl: i32, state: &mut ProbeState) -> i32 {
+		let board_key = board.material_key();
+
+		// KvK
+		if board.occupied_bitboard().count_ones() == 2 {
+			return WDL_DRAW;
+		}
+
+		let (tables, keys) =
+			if is_dtz {
+				(&mut self.dtz_tables, &self.dtz_keys)
+			} else {
+				(&mut self.wdl_tables, &self.wdl_keys)
+			};
+
+		let Some(&table_index) = keys.get(&board_key) else {
+			*state = ProbeState::Fail;
+			return 0;
+		};
+
+		let table = &mut tables[table_index];
+		if !table.ensure_loaded(&self.encoding) {
+			*state = ProbeState::Fail;
+			return 0;
+		}
+
+		let encoding = &self.encoding;
+
+		/*
+		Tables are made with the stronger side as white, and when both sides have the same pieces only white to move is stored,
+		so in those cases the colors get swapped and the board gets flipped
+		*/
+		let symmetric_black_to_move = table.key == table.key2 && !board.white_to_move;
+		let black_stronger = board_key != table.key;
+		let flip = symmetric_black_to_move || black_stronger;
+
+		let flip_color = if flip { 8 } else { 0 };
+		let flip_squares = if flip { 56 } else { 0 };
+		let side_to_move = flip as usize ^ (!board.white_to_move) as usize;
+
+		let mut squares = [0; MAX_TB_PIECES];
+		let mut pieces = [0; MAX_TB_PIECES];
+		let mut size = 0;
+		let mut lead_pawns = 0;
+		let mut lead_pawns_bitboard = 0;
+		let mut tb_file = 0;
+
+		// Tables with pawns are split into 4 by the file of the leading pawn
+		if table.has_pawns {
+			let lead_piece = table.get(0, 0).pieces[0] ^ flip_color;
+			let lead_white = lead_piece < 8;
+
+			lead_pawns_bitboard = board.piece_bitboards[build_piece(lead_white, PAWN)];
+			let mut bitboard = lead_pawns_bitboard;
+			while bitboard != 0 {
+				let i = bitboard.trailing_zeros() as usize;
+				bitboard &= bitboard - 1;
+
+				squares[size] = (i ^ 56) ^ flip_squares;
+				size += 1;
+			}
+
+			lead_pawns = size;
+
+			let mut lead = 0;
+			for i in 1..lead_pawns {
+				if encoding.map_pawns[squares[i]] > encoding.map_pawns[squares[lead]] {
+					lead = i;
+				}
+			}
+			squares.swap(0, lead);
+
+			tb_file = usize::min(file_of(squares[0]), 7 - file_of(squares[0]));
+		}
+
+		// DTZ tables only store one side to move
+		if table.is_dtz {
+			let flags = table.get(side_to_move, tb_file).flags;
+			if (flags & FLAG_STM) as usize != side_to_move
+			&& (table.key != table.key2 || table.has_pawns) {
+				*state = ProbeState::ChangeStm;
+				return 0;
+			}
+		}
+
+		for piece in 0..PIECE_COUNT {
+			let mut bitboard = board.piece_bitboards[piece] & !lead_pawns_bitboard;
+			let syzygy_piece = (get_piece_type(piece) + if is_piece_white(piece) { 1 } else { 9 }) as u8;
+
+			while bitboard != 0 {
+				let i = bitboard.trailing_zeros() as usize;
+				bitboard &= bitboard - 1;
+
+				squares[size] = (i ^ 56) ^ flip_squares;
+				pieces[size] = syzygy_piece ^ flip_color;
+				size += 1;
+			}
+		}
+
+		// Borrowing the field directly, so the file can still be borrowed later
+		let d = &table.items[if table.is_dtz { 0 } else { side_to_move }][if table.has_pawns { tb_file } else { 0 }];
+
+		// Put the pieces in the same order as the table
+		for i in lead_pawns..size - 1 {
+			for j in i + 1..size {
+				if d.pieces[i] == pieces[j] {
+					pieces.swap(i, j);
+					squares.swap(i, j);
+					break;
+				}
+			}
+		}
+
+		// The leading piece always goes on the a-d files
+		if file_of(squares[0]) > 3 {
+			for square in squares.iter_mut().take(size) {
+				*square ^= 7;
+			}
+		}
+
+		let mut index;
+		if table.has_pawns {
+			index = encoding.lead_pawn_index[lead_pawns][squares[0]];
+
+			squares[1..lead_pawns].sort_by_key(|square| encoding.map_pawns[*square]);
+
+			for (i, square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+				index += encoding.binomial[i][encoding.map_pawns[*square]];
+			}
+		} else {
+			// Without pawns the leading piece can also be flipped down to ranks 1-4...
+			if rank_of(squares[0]) > 3 {
+				for square in squares.iter_mut().take(size) {
+					*square ^= 56;
+				}
+			}
+
+			// ...and the first leading piece that isn't on the a1-h8 diagonal gets flipped below it
+			for i in 0..d.group_length[0] {
+				if off_diagonal(squares[i]) == 0 {
+					continue;
+				}
+
+				if off_diagonal(squares[i]) > 0 {
+					for square in squares.iter_mut().take(size).skip(i) {
+						*square = flip_diagonal(*square);
+					}
+				}
+
+				break;
+			}
+
+			if table.has_unique_pieces {
+				let adjust1 = (squares[1] > squares[0]) as usize;
+				let adjust2 = (squares[2] > squares[0]) as usize + (squares[2] > squares[1]) as usize;
+
+				index =
+					if off_diagonal(squares[0]) != 0 {
+						(encoding.map_a1d1d4[squares[0]] * 63 + (squares[1] - adjust1)) * 62 + squares[2] - adjust2
+					} else if off_diagonal(squares[1]) != 0 {
+						(6 * 63 + rank_of(squares[0]) * 28 + encoding.map_b1h1h7[squares[1]]) * 62 + squares[2] - adjust2
+					} else if off_diagonal(squares[2]) != 0 {
+						  6 * 63 * 62 + 4 * 28 * 62
+						+ rank_of(squares[0]) * 7 * 28
+						+ (rank_of(squares[1]) - adjust1) * 28
+						+ encoding.map_b1h1h7[squares[2]]
+					} else {
+						  6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28
+						+ rank_of(squares[0]) * 7 * 6
+						+ (rank_of(squares[1]) - adjust1) * 6
+						+ (rank_of(squares[2]) - adjust2)
+					} as u64;
+			} else {
+				index = encoding.map_kk[encoding.map_a1d1d4[squares[0]]][squares[1]] as u64;
+			}
+		}
+
+		// Then the rest of the groups, with each square shifted down by the squares taken by the groups before it
+		index *= d.group_index[0];
+
+		let mut group_start = d.group_length[0];
+		let mut remaining_pawns = table.has_pawns && table.pawn_count[1] > 0;
+		let mut next = 1;
+
+		while d.group_length[next] != 0 {
+			let group_end = group_start + d.group_length[next];
+			squares[group_start..group_end].sort();
+
+			let mut n = 0;
+			for i in 0..d.group_length[next] {
+				let square = squares[group_start + i];
+				let adjust = squares[..group_start].iter().filter(|s| square > **s).count();
+				n += encoding.binomial[i + 1][square - adjust - if remaining_pawns { 8 } else { 0 }];
+			}
+
+			remaining_pawns = false;
+			index += n * d.group_index[next];
+			group_start = group_end;
+			next += 1;
+		}
+
+		let Some(data) = &table.data else {
+			*state = ProbeState::Fail;
+			return 0;
+		};
+
+		let Some(value) = Table::decompress_pairs(data.bytes(), d, index) else {
+			*state = ProbeState::Fail;
+			return 0;
+		};
+
+		if !is_dtz {
+			return value as i32 - 2;
+		}
+
+		if let Some(dtz) = table.map_dtz(tb_file, value, wdl) {
+			dtz
+		} else {
+			*state = ProbeState::Fail;
+			0
+		}
+	}
+}
+
+// DTZ tables don't store captures and pawn moves, but the DTZ right before one is easy to work out from the WDL
+fn dtz_before_zeroing(wdl: i32) -> i32 {
+	match wdl {
+		WDL_WIN => 1,
+		WDL_CURSED_WIN => 101,
+		WDL_BLESSED_LOSS => -101,
+		WDL_LOSS => -1,
+		_ => 0,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::endgame_tablebase::EndgameTablebases;
+	use crate::utils::CHECKMATE_EVAL;
+
+	/*
+	Real tables can't be made without the Syzygy generator, so they have to be downloaded: the tests that need them
+	are skipped until KQvK, KRvK and KPvK (.rtbw and .rtbz) are in tests/syzygy, see tests/syzygy/README.txt
+	*/
+	const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy");
+
+	fn fixtures() -> Option<Syzygy> {
+		let syzygy = Syzygy::new(FIXTURES);
+		if syzygy.max_pieces < 3 {
+			eprintln!("Skipped, the 3-man Syzygy tables aren't in {}", FIXTURES);
+			return None;
+		}
+
+		Some(syzygy)
+	}
+
+	#[test]
+	fn decompresses_a_hand_made_table() {
+		// 2 bit codes for 4 symbols: 0, 1 and 2 are the values 0, 4 and 2, and 3 is the pair (1, 0)
+		let header = [
+			0, // Flags
+			5, 3, 0, // 32 byte blocks, a sparse index entry every 8 values, no padding
+			1, 0, 0, 0, // Block count
+			2, 2, // Max and min code length
+			0, 0, // Lowest symbol
+			4, 0, // Symbol count
+			0x00, 0xF0, 0xFF,
+			0x04, 0xF0, 0xFF,
+			0x02, 0xF0, 0xFF,
+			0x01, 0x00, 0x00,
+		];
+
+		let mut d = PairsData::default();
+		d.group_length[0] = 1;
+		d.group_index[1] = 10;
+		Table::set_sizes(&mut d, &mut HeaderReader { data: &header, offset: 0 }).unwrap();
+
+		assert_eq!(d.sparse_index_size, 2);
+		assert_eq!(d.symbol_lengths, [0, 0, 0, 1]);
+
+		// Codes 3 2 0 1 3 0 2 1
+		let mut file = vec![0; 96];
+		file[0..12].copy_from_slice(&[0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 12, 0]);
+		file[12..14].copy_from_slice(&[9, 0]);
+		file[64..66].copy_from_slice(&[0b11_10_00_01, 0b11_00_10_01]);
+
+		d.sparse_index = 0;
+		d.block_lengths = 12;
+		d.data = 64;
+
+		let values = (0..10).map(|i| Table::decompress_pairs(&file, &d, i)).collect::<Option<Vec<usize>>>();
+		assert_eq!(values, Some(vec![4, 0, 2, 0, 4, 4, 0, 0, 2, 4]));
+
+		d.flags = FLAG_SINGLE_VALUE;
+		d.min_symbol_length = 3;
+		assert_eq!(Table::decompress_pairs(&[], &d, 5), Some(3));
+	}
+
+	#[test]
+	fn probes_known_positions() {
+		let Some(mut syzygy) = fixtures() else {
+			return;
+		};
+
+		let mut probe = |fen: &str| {
+			let mut board = Board::from_fen(fen);
+			(syzygy.probe_wdl(&mut board).unwrap(), syzygy.probe_dtz(&mut board).unwrap())
+		};
+
+		// Qa8# and Rh8#
+		assert_eq!(probe("8/8/8/8/8/8/8/k1K4Q w - - 0 1"), (WDL_WIN, 1));
+		assert_eq!(probe("k7/8/1K6/8/8/8/8/7R w - - 0 1"), (WDL_WIN, 1));
+		assert_eq!(probe("8/8/8/8/8/8/8/k1K4Q b - - 0 1").0, WDL_LOSS);
+
+		// Promoting is a zeroing move
+		assert_eq!(probe("8/4P3/8/8/8/k7/8/K7 w - - 0 1"), (WDL_WIN, 1));
+		assert_eq!(probe("8/4P3/8/8/8/k7/8/K7 b - - 0 1").0, WDL_LOSS);
+
+		// The king in front of a rook pawn
+		assert_eq!(probe("8/8/8/8/8/k7/P7/K7 w - - 0 1"), (WDL_DRAW, 0));
+		assert_eq!(probe("8/8/8/8/8/k7/P7/K7 b - - 0 1"), (WDL_DRAW, 0));
+	}
+
+	#[test]
+	fn root_moves_keep_the_win() {
+		let Some(mut syzygy) = fixtures() else {
+			return;
+		};
+		let mut board = Board::from_fen("8/8/8/8/8/8/8/k1K4Q w - - 0 1");
+
+		let moves = syzygy.root_moves(&mut board).unwrap();
+		let coordinates = moves.iter().map(|m| m.to_coordinates()).collect::<Vec<String>>();
+
+		assert!(coordinates.contains(&"h1a8".to_string()));
+		// Stalemate
+		assert!(!coordinates.contains(&"h1h2".to_string()));
+
+		/*
+		3-man tables don't have any cursed wins, so this checks the same 50 move rule logic on a normal win instead:
+		with the counter at 99 only the fastest win is kept, and that's the mate
+		*/
+		board.board_state.current.fifty_move_counter = 99;
+		let moves = syzygy.root_moves(&mut board).unwrap();
+		assert!(moves.iter().all(|m| m.to_coordinates() == "h1a8"));
+	}
+
+	// Every 3-man position in the Syzygy tables has to agree with Maxwell's own retrograde tables
+	#[test]
+	fn matches_the_retrograde_tables() {
+		let Some(mut syzygy) = fixtures() else {
+			return;
+		};
+
+		let folder = std::env::temp_dir().join(format!("maxwell_syzygy_test_{}", std::process::id()));
+		let mut tablebases = EndgameTablebases::new(folder.to_str().unwrap());
+		for name in ["KQvK", "KRvK", "KPvK"] {
+			assert!(tablebases.generate(name));
+		}
+
+		let mut board = Board::from_fen(crate::STARTING_FEN);
+
+		for piece in [WHITE_QUEEN, WHITE_ROOK, WHITE_PAWN] {
+			for (white_king, black_king, square) in (0..64).flat_map(|a| (0..64).flat_map(move |b| (0..64).map(move |c| (a, b, c)))) {
+				if white_king == black_king
+				|| white_king == square
+				|| black_king == square
+				|| (piece == WHITE_PAWN && (square < 8 || square >= 56)) {
+					continue;
+				}
+
+				for white_to_move in [false, true] {
+					let position = (piece_to_char(piece), white_king, black_king, square, white_to_move);
+					board.set_pieces(&[(WHITE_KING, white_king), (BLACK_KING, black_king), (piece, square)], white_to_move);
+					if board.king_in_check(!white_to_move) {
+						continue;
+					}
+
+					let mate_score = tablebases.probe(&board, 0).unwrap();
+					let wdl = syzygy.probe_wdl(&mut board).unwrap();
+					assert_eq!(wdl.signum(), mate_score.signum(), "{:?} wdl {}", position, wdl);
+
+					// Without pawns, the only zeroing move is the mate
+					if piece != WHITE_PAWN
+					&& mate_score > 0 {
+						let dtz = syzygy.probe_dtz(&mut board).unwrap();
+						let plies_to_mate = CHECKMATE_EVAL - mate_score;
+						// Syzygy tables can store moves instead of plies, so they're allowed to be one ply off
+						assert!((dtz - plies_to_mate).abs() <= 1, "{:?} dtz {} mate in {}", position, dtz, plies_to_mate);
+					}
+				}
+			}
+		}
+
+		let _ = std::fs::remove_dir_all(folder);
+	}
+}
@@ -5,7 +5,7 @@ Thanks for the inspiration!
 
 use std::collections::HashMap;
 use crate::utils::evaluation_is_mate;
-use crate::move_data::MoveData;
+use crate::move_data::{MoveData, NULL_MOVE};
 use std::mem::size_of;
 
 pub const MEGABYTE: usize = 1024 * 1024;
@@ -128,7 +128,15 @@ impl TranspositionTable {
 				}
 			}
 
-			return (return_evaluation, Some(MoveData::from_binary(data.best_move)));
+			// Upper bounds from quiescence search can be stored without a best move
+			let hash_move =
+				if data.best_move == NULL_MOVE.to_binary() {
+					None
+				} else {
+					Some(MoveData::from_binary(data.best_move))
+				};
+
+			return (return_evaluation, hash_move);
 		}
 
 		(None, None)
@@ -0,0 +1,292 @@
+/*
+Texel's tuning method (https://www.chessprogramming.org/Texel%27s_Tuning_Method)
+
+The evaluation parameters get tuned so that the evaluations of a lot of positions predict the results of the games they came from:
+every evaluation is turned into an expected score with a sigmoid, and the parameters are changed one at a time by a local search,
+keeping every step that lowers the mean squared error
+
+Positions are one per line, a FEN followed by the game's result from white's perspective, like any of these:
+	<FEN> [1.0]
+	<FEN> 1/2-1/2
+	<FEN> c9 "0-1";
+*/
+
+use std::time::Instant;
+use crate::bot::{Bot, BotConfig};
+use crate::eval_params::EvalParams;
+use crate::pgn::parse_result;
+use crate::pieces::*;
+use crate::{Board, STARTING_FEN};
+
+pub const DEFAULT_EPOCHS: usize = 100;
+
+pub struct TunerConfig {
+	pub positions_file: String,
+	pub output: String,
+	// Parameters to start from instead of the defaults
+	pub params_file: String,
+	pub epochs: usize,
+	// Evaluate with the quiescence search instead of the static evaluation, for positions that aren't quiet
+	pub qsearch: bool,
+	pub threads: usize,
+}
+
+impl TunerConfig {
+	// tune <positions file> <output> (epochs=100) (qsearch=false) (params=<file>) (threads=<all>)
+	pub fn from_args(args: &[&str]) -> Option<Self> {
+		let mut config = Self {
+			positions_file: args.first()?.to_string(),
+			output: args.get(1)?.to_string(),
+			params_file: String::new(),
+			epochs: DEFAULT_EPOCHS,
+			qsearch: false,
+			threads: std::thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1),
+		};
+
+		for arg in args.iter().skip(2) {
+			match arg.split_once('=') {
+				Some(("epochs", value)) => config.epochs = value.parse().unwrap_or(DEFAULT_EPOCHS),
+				Some(("qsearch", value)) => config.qsearch = value == "true",
+				Some(("params", value)) => config.params_file = value.to_string(),
+				Some(("threads", value)) => config.threads = usize::max(1, value.parse().unwrap_or(1)),
+				_ => return None,
+			}
+		}
+
+		Some(config)
+	}
+}
+
+pub struct TuningPosition {
+	pub pieces: Vec<(usize, u8)>,
+	pub white_to_move: bool,
+	// 1.0 = white won, 0.5 = draw, 0.0 = black won
+	pub result: f32,
+}
+
+pub fn parse_position(line: &str) -> Option<TuningPosition> {
+	let fields = line.split_whitespace().collect::<Vec<&str>>();
+	if fields.len() < 3 {
+		return None;
+	}
+
+	let result = fields.last()?.trim_matches(|c| c == '[' || c == ']' || c == '"' || c == ';');
+	let result = parse_result(result).or_else(|| result.parse::<f32>().ok().filter(|result| (0.0..=1.0).contains(result)))?;
+
+	let mut pieces = vec![];
+	let mut i = 0;
+
+	for c in fields[0].chars() {
+		if let Some(empty_squares) = c.to_digit(10) {
+			i += empty_squares as u8;
+		} else if c != '/' {
+			let piece = char_to_piece(c);
+			if piece == NO_PIECE
+			|| i >= 64 {
+				return None;
+			}
+
+			pieces.push((piece, i));
+			i += 1;
+		}
+	}
+
+	// The evaluation needs both kings
+	for white in [false, true] {
+		if pieces.iter().filter(|(piece, _)| *piece == build_piece(white, KING)).count() != 1 {
+			return None;
+		}
+	}
+
+	Some(TuningPosition {
+		pieces,
+		white_to_move: fields[1] == "w",
+		result,
+	})
+}
+
+pub fn sigmoid(evaluation: i32, k: f64) -> f64 {
+	1.0 / (1.0 + 10f64.powf(-k * evaluation as f64 / 400.0))
+}
+
+pub struct Tuner {
+	pub positions: Vec<TuningPosition>,
+	pub qsearch: bool,
+	// One board per thread, and a Bot for the quiescence search
+	workers: Vec<(Board, Bot)>,
+}
+
+impl Tuner {
+	pub fn new(positions: Vec<TuningPosition>, qsearch: bool, threads: usize, bot_config: &BotConfig) -> Self {
+		let mut config = bot_config.clone();
+		config.debug_output = false;
+		config.opening_book = false;
+		config.hash_size = 0;
+		config.nnue = false;
+		// The quiescence search should only ever see the evaluation
+		config.syzygy_path = String::new();
+		config.tablebase_path = String::new();
+		config.experience_file = String::new();
+
+		let workers = (0..threads)
+			.map(|_| (Board::from_fen(STARTING_FEN), Bot::new(config.clone())))
+			.collect();
+
+		Self {
+			positions,
+			qsearch,
+			workers,
+		}
+	}
+
+	// From white's perspective, in the same order as the positions
+	pub fn evaluate_positions(&mut self, params: &EvalParams) -> Vec<i32> {
+		let chunk_size = self.positions.len().div_ceil(self.workers.len()).max(1);
+		let qsearch = self.qsearch;
+
+		std::thread::scope(|scope| {
+			let handles = self.workers.iter_mut()
+				.zip(self.positions.chunks(chunk_size))
+				.map(|((board, bot), positions)| {
+					board.set_eval_params(params.clone());
+
+					scope.spawn(move || {
+						positions.iter().map(|position| {
+							board.set_pieces(&position.pieces, position.white_to_move);
+
+							let evaluation = if qsearch { bot.quiet_evaluation(board) } else { board.hc_evaluate() };
+							evaluation * board.perspective()
+						}).collect::<Vec<i32>>()
+					})
+				})
+				.collect::<Vec<_>>();
+
+			handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+		})
+	}
+
+	pub fn params_error(&mut self, params: &EvalParams, k: f64) -> f64 {
+		let evaluations = self.evaluate_positions(params);
+		self.error(&evaluations, k)
+	}
+
+	pub fn error(&self, evaluations: &[i32], k: f64) -> f64 {
+		let total = self.positions.iter()
+			.zip(evaluations.iter())
+			.map(|(position, evaluation)| (position.result as f64 - sigmoid(*evaluation, k)).powi(2))
+			.sum::<f64>();
+
+		total / self.positions.len() as f64
+	}
+
+	// The K that makes the current evaluations fit the results best, everything after that is tuned with it
+	pub fn fit_k(&self, evaluations: &[i32]) -> f64 {
+		let mut k = 1.0;
+		let mut best_error = self.error(evaluations, k);
+
+		for step in [0.1, 0.01, 0.001] {
+			while let Some((new_k, error)) = [k + step, k - step].into_iter()
+				.filter(|k| *k > 0.0)
+				.map(|k| (k, self.error(evaluations, k)))
+				.find(|(_, error)| *error < best_error) {
+				k = new_k;
+				best_error = error;
+			}
+		}
+
+		k
+	}
+}
+
+pub fn tune(config: &TunerConfig, bot_config: &BotConfig) {
+	let timer = Instant::now();
+
+	let Ok(text) = std::fs::read_to_string(&config.positions_file) else {
+		println!("Failed to read {}", config.positions_file);
+		return;
+	};
+
+	let mut skipped = 0;
+	let positions = text.lines()
+		.filter(|line| !line.trim().is_empty())
+		.filter_map(|line| {
+			let position = parse_position(line);
+			if position.is_none() {
+				skipped += 1;
+			}
+			position
+		})
+		.collect::<Vec<TuningPosition>>();
+
+	if positions.is_empty() {
+		println!("No positions to tune with");
+		return;
+	}
+
+	println!("Loaded {} positions ({} skipped) in {} seconds", positions.len(), skipped, timer.elapsed().as_secs_f32());
+
+	let threads = usize::min(config.threads, positions.len());
+	let mut params = EvalParams::open(&config.params_file);
+	let mut tuner = Tuner::new(positions, config.qsearch, threads, bot_config);
+
+	let evaluations = tuner.evaluate_positions(&params);
+	let k = tuner.fit_k(&evaluations);
+	let mut best_error = tuner.error(&evaluations, k);
+	println!("K = {:.3}, error = {:.8}", k, best_error);
+
+	// Parameters that don't change the error at all (like the pawn table's back ranks) get skipped from then on
+	let mut unused = params.values().iter()
+		.map(|(_, values)| vec![false; values.len()])
+		.collect::<Vec<Vec<bool>>>();
+
+	for epoch in 1..=config.epochs {
+		let epoch_timer = Instant::now();
+		let mut changed = 0;
+
+		for (param, param_unused) in unused.iter_mut().enumerate() {
+			for (i, unused) in param_unused.iter_mut().enumerate() {
+				if *unused {
+					continue;
+				}
+
+				let original = params.values()[param].1.get(i);
+				let mut improved = false;
+				let mut same_error = true;
+
+				for delta in [1, -1] {
+					params.values()[param].1.set(i, original + delta);
+
+					let error = tuner.params_error(&params, k);
+					if error < best_error {
+						best_error = error;
+						improved = true;
+						break;
+					}
+
+					same_error &= error == best_error;
+				}
+
+				if improved {
+					changed += 1;
+				} else {
+					params.values()[param].1.set(i, original);
+					*unused = same_error;
+				}
+			}
+		}
+
+		// Saved after every epoch, so tuning can be stopped at any time
+		if let Err(error) = params.save(&config.output) {
+			println!("Failed to write {}: {}", config.output, error);
+			return;
+		}
+
+		println!("Epoch {}: error = {:.8}, changed {} parameters in {} seconds", epoch, best_error, changed, epoch_timer.elapsed().as_secs_f32());
+
+		if changed == 0 {
+			break;
+		}
+	}
+
+	println!("Saved the tuned parameters to {} in {} seconds, load them with the EvalFile option", config.output, timer.elapsed().as_secs_f32());
+}
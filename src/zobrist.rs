@@ -10,6 +10,8 @@ const SEED: u64 = 19274892; // old seed: 3141592653589793238
 
 pub struct Zobrist {
 	pub key: ValueHolder<u64>,
+	// Only the pawns, for the pawn hash table
+	pub pawn_key: ValueHolder<u64>,
 
 	pieces: [[u64; 64]; PIECE_COUNT],
 	castling_rights: [u64; 16],
@@ -22,6 +24,7 @@ impl Default for Zobrist {
 	fn default() -> Self {
 		Self {
 			key: ValueHolder::new(0),
+			pawn_key: ValueHolder::new(0),
 
 			pieces: [[0; 64]; PIECE_COUNT],
 			castling_rights: [0; 16],
@@ -54,27 +57,40 @@ impl Zobrist {
 		zobrist.side_to_move = rng.gen::<u64>();
 
 
-		let mut initial_key = 0;
+		let (initial_key, initial_pawn_key) = zobrist.calculate_keys(board);
+
+		zobrist.key = ValueHolder::new(initial_key);
+		zobrist.pawn_key = ValueHolder::new(initial_pawn_key);
+
+
+		zobrist
+	}
+
+	// The full key and the pawn key of a position from scratch
+	pub fn calculate_keys(&self, board: &Board) -> (u64, u64) {
+		let mut key = 0;
+		let mut pawn_key = 0;
 
 		for i in 0..64 {
 			let piece = board.get_piece(i);
 			if piece != NO_PIECE {
-				initial_key ^= zobrist.pieces[piece][i as usize];
+				key ^= self.pieces[piece][i as usize];
+
+				if get_piece_type(piece) == PAWN {
+					pawn_key ^= self.pieces[piece][i as usize];
+				}
 			}
 		}
 
-		initial_key ^= zobrist.castling_rights[board.board_state.current.castling_rights as usize];
+		key ^= self.castling_rights[board.board_state.current.castling_rights as usize];
 
-		initial_key ^= zobrist.en_passant[board.en_passant_file];
+		key ^= self.en_passant[board.en_passant_file];
 
 		if !board.white_to_move {
-			initial_key ^= zobrist.side_to_move;
+			key ^= self.side_to_move;
 		}
 
-		zobrist.key = ValueHolder::new(initial_key);
-
-
-		zobrist
+		(key, pawn_key)
 	}
 
 	// There's probably still bugs here; I'm very tired :`D
@@ -133,7 +149,33 @@ impl Zobrist {
 
 		self.key.current ^= self.side_to_move;
 
+		// Pawns leaving their square (by moving or promoting), arriving, and getting captured
+		if get_piece_type(data.piece as usize) == PAWN {
+			self.pawn_key.current ^= self.pieces[data.piece as usize][data.from as usize];
+
+			if !PROMOTABLE.contains(&data.flag) {
+				self.pawn_key.current ^= self.pieces[data.piece as usize][to];
+			}
+		}
+
+		if data.capture != NO_PIECE as u8
+		&& get_piece_type(data.capture as usize) == PAWN {
+			let capture_index = if data.flag == EN_PASSANT_FLAG {
+				if is_piece_white(data.piece as usize) { to + 8 } else { to - 8 }
+			} else {
+				to
+			};
+
+			self.pawn_key.current ^= self.pieces[data.capture as usize][capture_index];
+		}
+
 		self.key.push();
+		self.pawn_key.push();
+	}
+
+	pub fn undo_move(&mut self) {
+		self.key.pop();
+		self.pawn_key.pop();
 	}
 
 	pub fn make_null_move(&mut self) {
@@ -141,5 +183,6 @@ impl Zobrist {
 		self.key.current ^= self.en_passant[self.en_passant_file];
 
 		self.key.push();
+		self.pawn_key.push();
 	}
 }
\ No newline at end of file